@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use log::{info, warn};
+use teloxide::types::{ChatId, Update, UserId};
+
+use crate::config::Settings;
+
+/// Permission level of a Telegram user or chat. Ordered from least to most privileged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    /// May look at status information only
+    Viewer,
+    /// May change services (SSH on/off, unseal, ...)
+    Operator,
+    /// Full access
+    Admin,
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "viewer" => Ok(Role::Viewer),
+            "operator" => Ok(Role::Operator),
+            "admin" => Ok(Role::Admin),
+            other => Err(format!("unknown role '{}' (expected viewer, operator or admin)", other)),
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Role::Viewer => write!(f, "viewer"),
+            Role::Operator => write!(f, "operator"),
+            Role::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+/// Allow-list of Telegram user IDs and chat IDs, each mapped to a role.
+#[derive(Debug, Default, Clone)]
+pub struct AccessControl {
+    users: HashMap<u64, Role>,
+    chats: HashMap<i64, Role>,
+}
+
+impl AccessControl {
+    /// Reads `STB_ALLOWED_USERS` and `STB_ALLOWED_CHATS` (`id:role,id:role`).
+    /// The legacy `STB_ALLOWED_CHAT_ID` is still honoured and grants the admin role.
+    pub fn from_settings(settings: &Settings) -> Result<Self, Vec<String>> {
+        let mut acl = AccessControl::default();
+        let mut errors = Vec::new();
+
+        if let Some(value) = settings.get("STB_ALLOWED_USERS") {
+            let (entries, problems) = parse_entries("STB_ALLOWED_USERS", &value);
+            errors.extend(problems);
+            for (id, role) in entries {
+                match u64::try_from(id) {
+                    Ok(id) => { acl.users.insert(id, role); },
+                    Err(_) => errors.push(format!("STB_ALLOWED_USERS: user ID {} must be positive", id)),
+                }
+            }
+        }
+
+        if let Some(value) = settings.get("STB_ALLOWED_CHATS") {
+            let (entries, problems) = parse_entries("STB_ALLOWED_CHATS", &value);
+            errors.extend(problems);
+            acl.chats.extend(entries);
+        }
+
+        if let Some(value) = settings.get("STB_ALLOWED_CHAT_ID") {
+            match value.parse::<i64>() {
+                Ok(id) => { acl.chats.entry(id).or_insert(Role::Admin); },
+                Err(_) => errors.push(format!("STB_ALLOWED_CHAT_ID '{}' is not a numeric chat ID", value)),
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        if acl.users.is_empty() && acl.chats.is_empty() {
+            warn!("No users or chats are allowed to use the bot (STB_ALLOWED_USERS / STB_ALLOWED_CHATS / STB_ALLOWED_CHAT_ID not set)");
+        } else {
+            info!("Access control: {} user(s) and {} chat(s) allowed", acl.users.len(), acl.chats.len());
+        }
+
+        Ok(acl)
+    }
+
+    /// Role granted to a user in a chat: the higher of the user's and the chat's role.
+    pub fn role_for(&self, user: Option<UserId>, chat: Option<ChatId>) -> Option<Role> {
+        let user_role = user.and_then(|u| self.users.get(&u.0).copied());
+        let chat_role = chat.and_then(|c| self.chats.get(&c.0).copied());
+        user_role.max(chat_role)
+    }
+
+    pub fn role_for_update(&self, update: &Update) -> Option<Role> {
+        self.role_for(update.user().map(|u| u.id), update.chat().map(|c| c.id))
+    }
+
+    /// Chats that receive proactive messages: every allow-listed chat, or the private chats of
+    /// the allow-listed users if no chat is configured.
+    pub fn notification_chats(&self) -> Vec<ChatId> {
+        let mut chats: Vec<ChatId> = if self.chats.is_empty() {
+            self.users.keys().map(|&id| ChatId(id as i64)).collect()
+        } else {
+            self.chats.keys().map(|&id| ChatId(id)).collect()
+        };
+        chats.sort_by_key(|c| c.0);
+        chats
+    }
+}
+
+/// Parses a comma separated list of `id:role` entries. Entries without a role get the viewer role.
+/// Returns the valid entries and a message for every invalid one.
+fn parse_entries(var: &str, value: &str) -> (Vec<(i64, Role)>, Vec<String>) {
+    let mut entries = Vec::new();
+    let mut errors = Vec::new();
+    for entry in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (id, role) = match entry.split_once(':') {
+            Some((id, role)) => (id.trim(), role.parse::<Role>()),
+            None => (entry, Ok(Role::Viewer)),
+        };
+        match (id.parse::<i64>(), role) {
+            (Ok(id), Ok(role)) => entries.push((id, role)),
+            (Err(_), _) => errors.push(format!("{}: '{}' is not a numeric ID", var, id)),
+            (_, Err(e)) => errors.push(format!("{}: {} in entry '{}'", var, e, entry)),
+        }
+    }
+    (entries, errors)
+}
+
+/// Result of the authorization gate, injected into every handler behind it.
+#[derive(Debug, Clone, Copy)]
+pub struct Authorization {
+    /// Role of the caller, `None` if neither the user nor the chat is on the allow-list
+    pub granted: Option<Role>,
+    /// Minimum role the update needs
+    pub required: Role,
+    /// The user has an open unseal share link
+    pub share_pending: bool,
+}
+
+impl Authorization {
+    pub fn is_allowed(&self) -> bool {
+        self.granted.is_some_and(|role| role >= self.required)
+    }
+
+    /// A key holder who is not allowed on their own, e.g. in their private chat when only the
+    /// group that started the unseal has a role: just their share gets through the gate.
+    pub fn share_only(&self) -> bool {
+        self.share_pending && !self.is_allowed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_entries_with_and_without_role() {
+        let (entries, errors) = parse_entries("TEST", "1:admin, 2:Operator,3, -100:viewer,x:admin,4:root");
+        assert_eq!(entries, vec![
+            (1, Role::Admin),
+            (2, Role::Operator),
+            (3, Role::Viewer),
+            (-100, Role::Viewer),
+        ]);
+        assert_eq!(errors, vec![
+            "TEST: 'x' is not a numeric ID".to_string(),
+            "TEST: unknown role 'root' (expected viewer, operator or admin) in entry '4:root'".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn role_is_maximum_of_user_and_chat() {
+        let mut acl = AccessControl::default();
+        acl.users.insert(7, Role::Viewer);
+        acl.chats.insert(-42, Role::Operator);
+
+        assert_eq!(acl.role_for(Some(UserId(7)), Some(ChatId(7))), Some(Role::Viewer));
+        assert_eq!(acl.role_for(Some(UserId(7)), Some(ChatId(-42))), Some(Role::Operator));
+        assert_eq!(acl.role_for(Some(UserId(8)), Some(ChatId(-42))), Some(Role::Operator));
+        assert_eq!(acl.role_for(Some(UserId(8)), Some(ChatId(8))), None);
+    }
+
+    #[test]
+    fn authorization_requires_sufficient_role() {
+        let auth = |granted, required| Authorization { granted, required, share_pending: false };
+        assert!(auth(Some(Role::Admin), Role::Operator).is_allowed());
+        assert!(auth(Some(Role::Operator), Role::Operator).is_allowed());
+        assert!(!auth(Some(Role::Viewer), Role::Operator).is_allowed());
+        assert!(!auth(None, Role::Viewer).is_allowed());
+    }
+
+    #[test]
+    fn group_only_key_holder_may_submit_share() {
+        let mut acl = AccessControl::default();
+        acl.chats.insert(-42, Role::Operator);
+
+        // The holder joined in the group, the share arrives in their private chat
+        let granted = acl.role_for(Some(UserId(8)), Some(ChatId(8)));
+        assert_eq!(granted, None);
+        let holder = Authorization { granted, required: Role::Viewer, share_pending: true };
+        assert!(!holder.is_allowed());
+        assert!(holder.share_only());
+
+        let stranger = Authorization { share_pending: false, ..holder };
+        assert!(!stranger.share_only());
+        let allowed = Authorization { granted: Some(Role::Operator), ..holder };
+        assert!(!allowed.share_only());
+    }
+}
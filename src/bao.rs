@@ -1,11 +1,21 @@
-use reqwest::Client;
+use reqwest::{Client, Method, Response, StatusCode};
 use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use log::{info, warn};
+
+use crate::config::Settings;
+use crate::tls::TlsConfig;
 
 #[derive(Debug)]
 pub enum BaoError {
     Reqwest(reqwest::Error),
     Api(String),
+    // 503: the node is sealed or cannot serve the request right now
+    Unavailable(String),
 }
 
 impl fmt::Display for BaoError {
@@ -13,6 +23,7 @@ impl fmt::Display for BaoError {
         match self {
             BaoError::Reqwest(err) => write!(f, "HTTP error: {}", err),
             BaoError::Api(msg) => write!(f, "OpenBao API error: {}", msg),
+            BaoError::Unavailable(msg) => write!(f, "OpenBao unavailable: {}", msg),
         }
     }
 }
@@ -25,7 +36,7 @@ impl From<reqwest::Error> for BaoError {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct SealStatusInfo {
     pub initialized: bool,
     pub sealed: bool,
@@ -35,22 +46,214 @@ pub struct SealStatusInfo {
     pub t: u32,
 }
 
+/// `/v1/sys/health`, queried so that every node state answers with 200
+#[derive(Debug, Clone, Deserialize)]
+pub struct HealthInfo {
+    #[serde(default)]
+    pub standby: bool,
+    #[serde(default)]
+    pub performance_standby: bool,
+    #[serde(default)]
+    pub version: String,
+    #[serde(default)]
+    pub cluster_name: Option<String>,
+    #[serde(default)]
+    pub replication_performance_mode: Option<String>,
+    #[serde(default)]
+    pub replication_dr_mode: Option<String>,
+}
+
+/// `/v1/sys/leader` (only answered by unsealed nodes)
+#[derive(Debug, Clone, Deserialize)]
+pub struct LeaderInfo {
+    #[serde(default)]
+    pub ha_enabled: bool,
+    #[serde(default)]
+    pub is_self: bool,
+    #[serde(default)]
+    pub leader_address: String,
+}
+
+/// State of one cluster node for `/seal_status`
+#[derive(Debug)]
+pub struct NodeStatus {
+    pub addr: String,
+    pub seal: Result<SealStatusInfo, BaoError>,
+    pub health: Option<HealthInfo>,
+    pub leader: Option<LeaderInfo>,
+}
+
+impl NodeStatus {
+    /// Role of the node in the HA cluster, if it is known
+    pub fn role(&self) -> Option<&'static str> {
+        match (&self.leader, &self.health) {
+            (Some(leader), _) if leader.ha_enabled && leader.is_self => Some("leader"),
+            (_, Some(health)) if health.performance_standby => Some("perf standby"),
+            (_, Some(health)) if health.standby => Some("standby"),
+            _ => None,
+        }
+    }
+}
+
+/// `/v1/sys/storage/raft/autopilot/state`: health of the Raft peers as seen by the leader
+#[derive(Debug, Deserialize)]
+pub struct AutopilotState {
+    pub healthy: bool,
+    #[serde(default)]
+    pub failure_tolerance: u32,
+    #[serde(default)]
+    pub leader: String,
+    #[serde(default)]
+    pub servers: BTreeMap<String, AutopilotServer>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AutopilotServer {
+    #[serde(default)]
+    pub address: String,
+    #[serde(default)]
+    pub status: String,
+    pub healthy: bool,
+    #[serde(default)]
+    pub last_index: u64,
+    #[serde(default)]
+    pub last_contact: Option<String>,
+}
+
+/// `/v1/auth/token/lookup-self`
+#[derive(Debug, Deserialize)]
+pub struct TokenInfo {
+    #[serde(default)]
+    pub display_name: String,
+    #[serde(default)]
+    pub policies: Vec<String>,
+    // Seconds; 0 for a token that does not expire
+    #[serde(default)]
+    pub ttl: u64,
+    #[serde(default)]
+    pub renewable: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct DataResponse<T> {
+    data: T,
+}
+
+// `data` of a KV v2 read: the secret itself next to its metadata
+#[derive(Debug, Deserialize)]
+struct KvData {
+    data: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AuthResponse {
+    auth: AuthInfo,
+}
+
+#[derive(Debug, Deserialize)]
+struct AuthInfo {
+    client_token: String,
+    #[serde(default)]
+    lease_duration: u64,
+    #[serde(default)]
+    renewable: bool,
+}
+
 #[derive(Debug, Deserialize)]
 struct ApiErrorResponse {
     errors: Vec<String>,
 }
 
-/// Minimal OpenBao client for seal management (unauthenticated endpoints).
+// Turn an error response into a BaoError with the messages OpenBao sent
+async fn api_error(what: &str, resp: Response) -> BaoError {
+    let status = resp.status();
+    let body = resp.text().await.unwrap_or_default();
+    let msg = serde_json::from_str::<ApiErrorResponse>(&body)
+        .map(|e| e.errors.join("; "))
+        .unwrap_or(body);
+    let msg = format!("{} returned {}: {}", what, status, msg);
+    if status == StatusCode::SERVICE_UNAVAILABLE {
+        BaoError::Unavailable(msg)
+    } else {
+        BaoError::Api(msg)
+    }
+}
+
+/// How the bot gets a token for the authenticated endpoints
+#[derive(Debug, Clone)]
+pub enum BaoAuth {
+    /// Token kept in a file, e.g. written by an OpenBao agent; read again for every login
+    TokenFile(PathBuf),
+    AppRole { mount: String, role_id: String, secret_id: String },
+}
+
+impl BaoAuth {
+    /// Reads `STB_BAO_TOKEN_FILE`, or `STB_BAO_ROLE_ID` and `STB_BAO_SECRET_ID` (both also as
+    /// `_FILE`) with the AppRole mount in `STB_BAO_APPROLE_MOUNT` (default `approle`).
+    /// `None` if neither is set.
+    pub fn from_settings(settings: &Settings) -> Result<Option<Self>, String> {
+        if let Some(path) = settings.get("STB_BAO_TOKEN_FILE") {
+            return Ok(Some(BaoAuth::TokenFile(PathBuf::from(path))));
+        }
+        match (settings.secret("STB_BAO_ROLE_ID")?, settings.secret("STB_BAO_SECRET_ID")?) {
+            (Some(role_id), Some(secret_id)) => Ok(Some(BaoAuth::AppRole {
+                mount: settings.get("STB_BAO_APPROLE_MOUNT").unwrap_or_else(|| "approle".to_string()),
+                role_id,
+                secret_id,
+            })),
+            (None, None) => Ok(None),
+            _ => Err("STB_BAO_ROLE_ID and STB_BAO_SECRET_ID must be set together".to_string()),
+        }
+    }
+}
+
+// The current token and what OpenBao said about its lifetime
+#[derive(Debug, Clone)]
+struct TokenLease {
+    token: String,
+    // Zero for a token that does not expire
+    ttl: Duration,
+    renewable: bool,
+}
+
+impl From<AuthInfo> for TokenLease {
+    fn from(auth: AuthInfo) -> Self {
+        TokenLease {
+            token: auth.client_token,
+            ttl: Duration::from_secs(auth.lease_duration),
+            renewable: auth.renewable,
+        }
+    }
+}
+
+// Renewal of the token in the background
+const TOKEN_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+const TOKEN_CHECK_INTERVAL: Duration = Duration::from_secs(3600);
+const MIN_RENEW_INTERVAL: Duration = Duration::from_secs(5);
+// A renewal that leaves less than this is treated as the end of the token's max TTL
+const MIN_TOKEN_TTL: Duration = Duration::from_secs(60);
+
+// Time until the next renewal: half the TTL, so one failed attempt still leaves time to retry
+fn renew_delay(ttl: Duration) -> Duration {
+    if ttl.is_zero() {
+        TOKEN_CHECK_INTERVAL
+    } else {
+        (ttl / 2).max(MIN_RENEW_INTERVAL)
+    }
+}
+
+/// Minimal OpenBao client for seal management; the few authenticated calls get the token passed in.
 pub struct BaoClient {
     addr: String,
     http: Client,
 }
 
 impl BaoClient {
-    pub fn new(addr: &str) -> Self {
+    pub fn new(addr: &str, tls: &TlsConfig) -> Self {
+        let (builder, addr) = tls.apply(Client::builder(), addr.trim_end_matches('/'));
         BaoClient {
-            addr: addr.trim_end_matches('/').to_string(),
-            http: Client::new(),
+            addr,
+            http: builder.build().expect("Failed to build reqwest client"),
         }
     }
 
@@ -63,6 +266,32 @@ impl BaoClient {
         Ok(resp.json::<SealStatusInfo>().await?)
     }
 
+    pub fn addr(&self) -> &str {
+        &self.addr
+    }
+
+    pub async fn health(&self) -> Result<HealthInfo, BaoError> {
+        // Map standby, sealed and uninitialized to 200 so the body is always returned
+        let url = format!(
+            "{}/v1/sys/health?standbyok=true&perfstandbyok=true&sealedcode=200&uninitcode=200&drsecondarycode=200",
+            self.addr
+        );
+        let resp = self.http.get(&url).send().await?;
+        if !resp.status().is_success() {
+            return Err(BaoError::Api(format!("health returned {}", resp.status())));
+        }
+        Ok(resp.json::<HealthInfo>().await?)
+    }
+
+    pub async fn leader(&self) -> Result<LeaderInfo, BaoError> {
+        let url = format!("{}/v1/sys/leader", self.addr);
+        let resp = self.http.get(&url).send().await?;
+        if !resp.status().is_success() {
+            return Err(BaoError::Api(format!("leader returned {}", resp.status())));
+        }
+        Ok(resp.json::<LeaderInfo>().await?)
+    }
+
     pub async fn unseal(&self, key: &str) -> Result<SealStatusInfo, BaoError> {
         let url = format!("{}/v1/sys/unseal", self.addr);
         let resp = self.http
@@ -71,15 +300,285 @@ impl BaoClient {
             .send()
             .await?;
         if !resp.status().is_success() {
-            let status = resp.status();
-            let body = resp.text().await.unwrap_or_default();
-            let msg = serde_json::from_str::<ApiErrorResponse>(&body)
-                .map(|e| e.errors.join("; "))
-                .unwrap_or(body);
-            return Err(BaoError::Api(format!("unseal returned {}: {}", status, msg)));
+            return Err(api_error("unseal", resp).await);
         }
         Ok(resp.json::<SealStatusInfo>().await?)
     }
+
+    // Request to `/v1/<path>`, with the token if given; error responses become a BaoError
+    async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        token: Option<&str>,
+        body: Option<&serde_json::Value>
+    ) -> Result<Response, BaoError> {
+        let mut req = self.http.request(method, format!("{}/v1/{}", self.addr, path));
+        if let Some(token) = token {
+            req = req.header("X-Vault-Token", token);
+        }
+        if let Some(body) = body {
+            req = req.json(body);
+        }
+        let resp = req.send().await?;
+        if !resp.status().is_success() {
+            return Err(api_error(path, resp).await);
+        }
+        Ok(resp)
+    }
+
+    pub async fn seal(&self, token: &str) -> Result<SealStatusInfo, BaoError> {
+        self.send(Method::PUT, "sys/seal", Some(token), None).await?;
+        self.seal_status().await
+    }
+}
+
+/// All nodes of an OpenBao cluster. With Raft every node keeps its own seal, so each one has
+/// to be unsealed with the threshold of shares.
+pub struct BaoCluster {
+    nodes: Vec<BaoClient>,
+    auth: Option<BaoAuth>,
+    token: Mutex<Option<TokenLease>>,
+}
+
+impl BaoCluster {
+    /// Comma separated node addresses, e.g. `https://bao1:8200,https://bao2:8200`
+    pub fn new(addrs: &str, tls: &TlsConfig, auth: Option<BaoAuth>) -> Self {
+        BaoCluster {
+            nodes: addrs
+                .split(',')
+                .map(str::trim)
+                .filter(|a| !a.is_empty())
+                .map(|addr| BaoClient::new(addr, tls))
+                .collect(),
+            auth,
+            token: Mutex::new(None),
+        }
+    }
+
+    pub fn nodes(&self) -> &[BaoClient] {
+        &self.nodes
+    }
+
+    /// Seal status of the cluster as a whole: the first sealed node, otherwise the first
+    /// reachable one. Fails only if no node can be reached.
+    pub async fn seal_status(&self) -> Result<SealStatusInfo, BaoError> {
+        let mut first_ok = None;
+        let mut last_err = None;
+        for node in &self.nodes {
+            match node.seal_status().await {
+                Ok(status) if status.sealed => return Ok(status),
+                Ok(status) => { first_ok.get_or_insert(status); },
+                Err(e) => last_err = Some(e),
+            }
+        }
+        first_ok.ok_or_else(|| last_err.unwrap_or_else(|| BaoError::Api("no OpenBao node configured".to_string())))
+    }
+
+    /// Seal, health and leader information of every node
+    pub async fn node_status(&self) -> Vec<NodeStatus> {
+        let mut nodes = Vec::new();
+        for node in &self.nodes {
+            let seal = node.seal_status().await;
+            let (health, leader) = if seal.is_ok() {
+                (node.health().await.ok(), node.leader().await.ok())
+            } else {
+                (None, None)
+            };
+            nodes.push(NodeStatus { addr: node.addr().to_string(), seal, health, leader });
+        }
+        nodes
+    }
+
+    /// Submit an unseal key to every node that is still sealed. Nodes that are already
+    /// unsealed report their current status.
+    pub async fn unseal_all(&self, key: &str) -> Vec<(String, Result<SealStatusInfo, BaoError>)> {
+        let mut results = Vec::new();
+        for node in &self.nodes {
+            let result = match node.seal_status().await {
+                Ok(status) if !status.sealed => Ok(status),
+                Ok(_) => node.unseal(key).await,
+                Err(e) => Err(e),
+            };
+            results.push((node.addr().to_string(), result));
+        }
+        results
+    }
+
+    pub fn has_auth(&self) -> bool {
+        self.auth.is_some()
+    }
+
+    // Authenticated endpoints can be called on any unsealed node, standbys forward them.
+    // Nodes that are unreachable or sealed are skipped.
+    async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        token: Option<&str>,
+        body: Option<&serde_json::Value>
+    ) -> Result<Response, BaoError> {
+        let mut last_err = BaoError::Api("no OpenBao node configured".to_string());
+        for node in &self.nodes {
+            match node.send(method.clone(), path, token, body).await {
+                Err(e @ (BaoError::Reqwest(_) | BaoError::Unavailable(_))) => last_err = e,
+                result => return result,
+            }
+        }
+        Err(last_err)
+    }
+
+    // The current token; logs in first if there is none yet (e.g. OpenBao was sealed so far)
+    async fn token(&self) -> Result<String, BaoError> {
+        let Some(auth) = &self.auth else {
+            return Err(BaoError::Api("no token configured (STB_BAO_TOKEN_FILE or STB_BAO_ROLE_ID/STB_BAO_SECRET_ID)".to_string()));
+        };
+        let mut token = self.token.lock().await;
+        if token.is_none() {
+            *token = Some(self.login(auth).await?);
+        }
+        Ok(token.as_ref().map(|lease| lease.token.clone()).unwrap_or_default())
+    }
+
+    async fn login(&self, auth: &BaoAuth) -> Result<TokenLease, BaoError> {
+        let lease = match auth {
+            BaoAuth::TokenFile(path) => {
+                let token = crate::secrets::read_secret_file(path)
+                    .map_err(|e| BaoError::Api(format!("token file: {}", e)))?;
+                let info = self.lookup(&token).await?;
+                TokenLease { token, ttl: Duration::from_secs(info.ttl), renewable: info.renewable }
+            },
+            BaoAuth::AppRole { mount, role_id, secret_id } => {
+                let body = serde_json::json!({ "role_id": role_id, "secret_id": secret_id });
+                let resp = self.send(Method::POST, &format!("auth/{}/login", mount), None, Some(&body)).await?;
+                resp.json::<AuthResponse>().await?.auth.into()
+            },
+        };
+        info!("Logged in to OpenBao (token TTL {}s, renewable: {})", lease.ttl.as_secs(), lease.renewable);
+        Ok(lease)
+    }
+
+    async fn lookup(&self, token: &str) -> Result<TokenInfo, BaoError> {
+        let resp = self.send(Method::GET, "auth/token/lookup-self", Some(token), None).await?;
+        Ok(resp.json::<DataResponse<TokenInfo>>().await?.data)
+    }
+
+    async fn renew(&self, token: &str) -> Result<TokenLease, BaoError> {
+        let resp = self.send(Method::POST, "auth/token/renew-self", Some(token), None).await?;
+        Ok(resp.json::<AuthResponse>().await?.auth.into())
+    }
+
+    // Renew the token, or log in again if it cannot be renewed any more. Returns the new TTL.
+    async fn refresh_token(&self, auth: &BaoAuth) -> Result<Duration, BaoError> {
+        let mut token = self.token.lock().await;
+        if let Some(lease) = token.as_mut() {
+            let refreshed = if lease.renewable {
+                self.renew(&lease.token).await
+            } else if lease.ttl.is_zero() {
+                // A token without expiry (e.g. root) only has to stay valid
+                self.lookup(&lease.token).await.map(|_| lease.clone())
+            } else {
+                Err(BaoError::Api("token is not renewable".to_string()))
+            };
+            match refreshed {
+                Ok(renewed) if renewed.ttl.is_zero() || renewed.ttl >= MIN_TOKEN_TTL => {
+                    *lease = renewed;
+                    return Ok(lease.ttl);
+                },
+                Ok(_) => info!("OpenBao token reaches its maximum TTL, logging in again"),
+                Err(e) => info!("OpenBao token not renewed ({}), logging in again", e),
+            }
+        }
+        let lease = self.login(auth).await?;
+        let ttl = lease.ttl;
+        *token = Some(lease);
+        Ok(ttl)
+    }
+
+    /// Keep the token valid: renew it at half its TTL and log in again once it cannot be
+    /// renewed any more. Runs forever; returns right away without an auth method.
+    pub async fn maintain_token(&self) {
+        let Some(auth) = &self.auth else {
+            return;
+        };
+        loop {
+            let wait = match self.refresh_token(auth).await {
+                Ok(ttl) => renew_delay(ttl),
+                Err(e) => {
+                    // Expected while OpenBao is sealed
+                    warn!("OpenBao token refresh failed, retrying in {}s: {}", TOKEN_RETRY_INTERVAL.as_secs(), e);
+                    TOKEN_RETRY_INTERVAL
+                }
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Policies and remaining TTL of the bot's token
+    pub async fn token_info(&self) -> Result<TokenInfo, BaoError> {
+        let token = self.token().await?;
+        self.lookup(&token).await
+    }
+
+    /// Raft autopilot view of the cluster (needs a token with read access to `sys/storage/raft`)
+    pub async fn autopilot_state(&self) -> Result<AutopilotState, BaoError> {
+        let token = self.token().await?;
+        let resp = self.send(Method::GET, "sys/storage/raft/autopilot/state", Some(&token), None).await?;
+        Ok(resp.json::<DataResponse<AutopilotState>>().await?.data)
+    }
+
+    /// String fields of the latest version of a KV v2 secret, e.g. mount `secret`, path `synology`
+    pub async fn read_kv(&self, mount: &str, path: &str) -> Result<HashMap<String, String>, BaoError> {
+        let token = self.token().await?;
+        let path = format!("{}/data/{}", mount.trim_matches('/'), path.trim_matches('/'));
+        let resp = self.send(Method::GET, &path, Some(&token), None).await?;
+        Ok(kv_strings(resp.json::<DataResponse<KvData>>().await?.data))
+    }
+
+    /// Seal every unsealed node. Standbys go first, so that none of them takes over once
+    /// the active node is sealed. Fails as a whole only if there is no token.
+    pub async fn seal_all(&self) -> Result<Vec<(String, Result<SealStatusInfo, BaoError>)>, BaoError> {
+        let token = self.token().await?;
+        let mut nodes = Vec::new();
+        for node in &self.nodes {
+            let is_leader = node.leader().await.is_ok_and(|l| l.is_self);
+            nodes.push((is_leader, node));
+        }
+        nodes.sort_by_key(|(is_leader, _)| *is_leader);
+
+        let mut results = Vec::new();
+        for (_, node) in nodes {
+            let result = match node.seal_status().await {
+                Ok(status) if status.sealed => Ok(status),
+                Ok(_) => node.seal(&token).await,
+                Err(e) => Err(e),
+            };
+            results.push((node.addr().to_string(), result));
+        }
+        Ok(results)
+    }
+
+    /// Ask the active node to give up leadership; a standby takes over
+    pub async fn step_down(&self) -> Result<String, BaoError> {
+        let token = self.token().await?;
+        for node in &self.nodes {
+            if node.leader().await.is_ok_and(|l| l.is_self) {
+                node.send(Method::PUT, "sys/step-down", Some(&token), None).await?;
+                return Ok(node.addr().to_string());
+            }
+        }
+        Err(BaoError::Api("no active node found".to_string()))
+    }
+}
+
+fn kv_strings(kv: KvData) -> HashMap<String, String> {
+    kv.data
+        .into_iter()
+        .filter_map(|(key, value)| match value {
+            serde_json::Value::String(value) => Some((key, value)),
+            _ => None,
+        })
+        .collect()
 }
 
 /// Ephemeral X25519 keypair for one unseal challenge (lives only in RAM).
@@ -101,14 +600,24 @@ pub fn random_session_id() -> String {
     bytes.iter().map(|b| format!("{:02x}", b)).collect()
 }
 
-/// Decrypt an age-armored ciphertext with an ephemeral identity.
+pub const AGE_ARMOR_HEADER: &str = "-----BEGIN AGE ENCRYPTED FILE-----";
+const AGE_BINARY_HEADER: &[u8] = b"age-encryption.org/v1\n";
+
+/// True if the data starts like an age file, ASCII-armored or binary.
+pub fn is_age_ciphertext(data: &[u8]) -> bool {
+    let data = data.trim_ascii_start();
+    data.starts_with(AGE_ARMOR_HEADER.as_bytes()) || data.starts_with(AGE_BINARY_HEADER)
+}
+
+/// Decrypt an age ciphertext (ASCII-armored or binary) with an ephemeral identity.
 pub fn decrypt_ciphertext(
-    ciphertext: &str,
+    ciphertext: impl AsRef<[u8]>,
     identity: &age::x25519::Identity,
 ) -> Result<String, BaoError> {
     use std::io::Read;
 
-    let armored = age::armor::ArmoredReader::new(ciphertext.as_bytes());
+    // ArmoredReader passes binary input through unchanged
+    let armored = age::armor::ArmoredReader::new(ciphertext.as_ref());
     let decryptor = age::Decryptor::new(armored)
         .map_err(|e| BaoError::Api(format!("invalid age ciphertext: {}", e)))?;
     let mut reader = decryptor
@@ -145,13 +654,32 @@ mod tests {
             writer.finish().unwrap().finish().unwrap();
         }
         let decrypted = decrypt_ciphertext(
-            &String::from_utf8(ciphertext).unwrap(),
+            String::from_utf8(ciphertext).unwrap(),
             &key.identity,
         )
         .unwrap();
         assert_eq!(decrypted, "roundtrip-secret");
     }
 
+    #[test]
+    fn decrypts_binary_age_file() {
+        let key = generate_ephemeral_key();
+        let recipient: age::x25519::Recipient = key.recipient.parse().expect("valid recipient");
+        let encryptor = age::Encryptor::with_recipients(
+            std::iter::once(&recipient as &dyn age::Recipient),
+        ).expect("encryptor");
+        let mut ciphertext = vec![];
+        {
+            use std::io::Write;
+            let mut writer = encryptor.wrap_output(&mut ciphertext).unwrap();
+            writer.write_all(b"binary-secret").unwrap();
+            writer.finish().unwrap();
+        }
+        assert!(is_age_ciphertext(&ciphertext));
+        assert!(!is_age_ciphertext(b"hvs.plain-token"));
+        assert_eq!(decrypt_ciphertext(&ciphertext, &key.identity).unwrap(), "binary-secret");
+    }
+
     /// Interop: ciphertext produced by the official age JS implementation
     /// (same library the web app bundles) must be decryptable here.
     #[test]
@@ -168,6 +696,68 @@ mod tests {
         assert_eq!(decrypted, "test-secret-123");
     }
 
+    #[test]
+    fn cluster_takes_comma_separated_nodes() {
+        let cluster = BaoCluster::new("https://bao1:8200/, https://bao2:8200,,", &TlsConfig::default(), None);
+        let addrs: Vec<&str> = cluster.nodes().iter().map(|n| n.addr()).collect();
+        assert_eq!(addrs, vec!["https://bao1:8200", "https://bao2:8200"]);
+    }
+
+    #[test]
+    fn node_role_from_leader_and_health() {
+        let health: HealthInfo = serde_json::from_str(
+            r#"{"initialized":true,"sealed":false,"standby":true,"performance_standby":false,"version":"2.1.0"}"#
+        ).unwrap();
+        let leader: LeaderInfo = serde_json::from_str(
+            r#"{"ha_enabled":true,"is_self":false,"leader_address":"https://bao1:8200"}"#
+        ).unwrap();
+        let mut node = NodeStatus {
+            addr: "https://bao2:8200".to_string(),
+            seal: Ok(SealStatusInfo { initialized: true, sealed: false, progress: 0, t: 3 }),
+            health: Some(health),
+            leader: Some(leader),
+        };
+        assert_eq!(node.role(), Some("standby"));
+        node.leader.as_mut().unwrap().is_self = true;
+        assert_eq!(node.role(), Some("leader"));
+        node.leader = None;
+        node.health = None;
+        assert_eq!(node.role(), None);
+    }
+
+    #[test]
+    fn parses_autopilot_state_and_token_lease() {
+        let state: DataResponse<AutopilotState> = serde_json::from_str(r#"{"data":{
+            "healthy":true,"failure_tolerance":1,"leader":"bao1",
+            "servers":{"bao1":{"id":"bao1","address":"10.0.0.1:8201","status":"leader","healthy":true,"last_index":42},
+                       "bao2":{"id":"bao2","address":"10.0.0.2:8201","status":"voter","healthy":false,"last_index":40,"last_contact":"4.2s"}}
+        }}"#).unwrap();
+        assert_eq!(state.data.failure_tolerance, 1);
+        assert_eq!(state.data.servers["bao2"].status, "voter");
+        assert!(!state.data.servers["bao2"].healthy);
+
+        let auth: AuthResponse = serde_json::from_str(
+            r#"{"auth":{"client_token":"s.abc","policies":["stb"],"lease_duration":3600,"renewable":true}}"#
+        ).unwrap();
+        let lease = TokenLease::from(auth.auth);
+        assert_eq!(lease.token, "s.abc");
+        assert_eq!(renew_delay(lease.ttl), Duration::from_secs(1800));
+        assert_eq!(renew_delay(Duration::from_secs(4)), MIN_RENEW_INTERVAL);
+        assert_eq!(renew_delay(Duration::ZERO), TOKEN_CHECK_INTERVAL);
+    }
+
+    #[test]
+    fn kv_v2_secret_keeps_string_fields() {
+        let resp: DataResponse<KvData> = serde_json::from_str(r#"{"data":{
+            "data":{"username":"stb","password":"p\\w","port":5001},
+            "metadata":{"version":3,"destroyed":false}
+        }}"#).unwrap();
+        let fields = kv_strings(resp.data);
+        assert_eq!(fields.get("username").map(String::as_str), Some("stb"));
+        assert_eq!(fields.get("password").map(String::as_str), Some("p\\w"));
+        assert!(!fields.contains_key("port"));
+    }
+
     #[test]
     fn session_id_is_32_hex_chars() {
         let id = random_session_id();
@@ -0,0 +1,535 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use reqwest::Url;
+
+use crate::auth::AccessControl;
+use crate::bao::BaoAuth;
+use crate::monitor::MonitorConfig;
+use crate::secrets;
+use crate::ssh_timer::parse_duration;
+use crate::synology::{find_service, DEFAULT_SESSION_IDLE_TIMEOUT};
+use crate::tls::TlsConfig;
+use crate::web::UnsealWebConfig;
+use crate::wol::WolTarget;
+
+const DEFAULT_STATE_DIR: &str = "data";
+const DEFAULT_NAS_NAME: &str = "main";
+
+/// Everything that is wrong with the configuration, reported together at startup
+#[derive(Debug)]
+pub struct ConfigError(Vec<String>);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid configuration:")?;
+        for problem in &self.0 {
+            write!(f, "\n  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Raw settings by their environment variable name. The config file (`STB_CONFIG_FILE`, TOML or
+/// YAML) maps to the same names: `[synology] nas_base_url` is `STB_SYNOLOGY_NAS_BASE_URL`,
+/// `[bao.tls] ca` is `STB_BAO_TLS_CA`. A non-empty environment variable wins over the file.
+pub struct Settings {
+    file: Option<PathBuf>,
+    // Name -> (key in the file, value)
+    values: BTreeMap<String, (String, String)>,
+    env: HashMap<String, String>,
+    used: RefCell<HashSet<String>>,
+}
+
+impl Settings {
+    pub fn load() -> Result<Self, ConfigError> {
+        let env: HashMap<String, String> = std::env::vars().filter(|(name, _)| name.starts_with("STB_")).collect();
+        let Some(file) = env.get("STB_CONFIG_FILE").map(|v| v.trim()).filter(|v| !v.is_empty()) else {
+            return Ok(Settings::new(None, BTreeMap::new(), env));
+        };
+        let path = PathBuf::from(file);
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| ConfigError(vec![format!("cannot read config file {}: {}", path.display(), e)]))?;
+        let values = parse_file(&path, &content)
+            .map_err(|e| ConfigError(vec![format!("config file {}: {}", path.display(), e)]))?;
+        Ok(Settings::new(Some(path), values, env))
+    }
+
+    fn new(file: Option<PathBuf>, values: BTreeMap<String, (String, String)>, env: HashMap<String, String>) -> Self {
+        Settings { file, values, env, used: RefCell::new(HashSet::new()) }
+    }
+
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
+    /// Value of a setting, trimmed; empty counts as unset
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.used.borrow_mut().insert(name.to_string());
+        let value = |v: &String| Some(v.trim().to_string()).filter(|v| !v.is_empty());
+        self.env.get(name)
+            .and_then(value)
+            .or_else(|| self.values.get(name).and_then(|(_, v)| value(v)))
+    }
+
+    /// A secret, given directly or as `<NAME>_FILE`, see `secrets::resolve`
+    pub fn secret(&self, name: &str) -> Result<Option<String>, String> {
+        secrets::resolve(name, self.get(name), self.get(&format!("{}_FILE", name))).map_err(|e| e.to_string())
+    }
+
+    /// Keys of the config file that no part of the bot has asked for, most likely typos
+    pub fn unused_keys(&self) -> Vec<String> {
+        let used = self.used.borrow();
+        self.values
+            .iter()
+            .filter(|(name, _)| !used.contains(*name))
+            .map(|(_, (key, _))| key.clone())
+            .collect()
+    }
+}
+
+// Flatten a TOML or YAML file to environment variable names
+fn parse_file(path: &Path, content: &str) -> Result<BTreeMap<String, (String, String)>, String> {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_lowercase();
+    let document: serde_json::Value = match extension.as_str() {
+        "toml" => toml::from_str(content).map_err(|e| e.to_string())?,
+        "yaml" | "yml" => serde_yaml::from_str(content).map_err(|e| e.to_string())?,
+        _ => return Err("unknown format, use a .toml, .yaml or .yml file".to_string()),
+    };
+    let mut values = BTreeMap::new();
+    match &document {
+        serde_json::Value::Object(_) => flatten("", &document, &mut values)?,
+        serde_json::Value::Null => {},
+        _ => return Err("expected a table of settings".to_string()),
+    }
+    Ok(values)
+}
+
+fn flatten(key: &str, value: &serde_json::Value, values: &mut BTreeMap<String, (String, String)>) -> Result<(), String> {
+    use serde_json::Value;
+
+    let scalar = |value: &Value| match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    };
+    match value {
+        Value::Object(table) => {
+            for (name, value) in table {
+                let key = if key.is_empty() { name.clone() } else { format!("{}.{}", key, name) };
+                flatten(&key, value, values)?;
+            }
+        },
+        Value::Array(items) => {
+            // Lists become the comma separated form of the environment variable
+            let items = items.iter().map(scalar).collect::<Option<Vec<_>>>()
+                .ok_or_else(|| format!("{}: lists may only contain plain values", key))?;
+            values.insert(env_name(key), (key.to_string(), items.join(",")));
+        },
+        Value::Null => {},
+        value => {
+            values.insert(env_name(key), (key.to_string(), scalar(value).unwrap_or_default()));
+        },
+    }
+    Ok(())
+}
+
+// "synology.nas_base_url" -> "STB_SYNOLOGY_NAS_BASE_URL"
+fn env_name(key: &str) -> String {
+    format!("STB_{}", key.replace(['.', '-'], "_").to_uppercase())
+}
+
+/// An http(s) URL with a host, returned without trailing slash
+pub fn parse_url(name: &str, value: &str) -> Result<String, String> {
+    match Url::parse(value) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.host().is_some() => Ok(value.trim_end_matches('/').to_string()),
+        Ok(_) => Err(format!("{} '{}' must be an http:// or https:// URL with a host", name, value)),
+        Err(e) => Err(format!("{} '{}' is not a valid URL: {}", name, value, e)),
+    }
+}
+
+pub fn parse_bool(name: &str, value: &str) -> Result<bool, String> {
+    match value.to_lowercase().as_str() {
+        "true" | "1" | "yes" | "on" => Ok(true),
+        "false" | "0" | "no" | "off" => Ok(false),
+        _ => Err(format!("{} '{}' must be true or false", name, value)),
+    }
+}
+
+/// DSM connection and login of one NAS profile
+pub struct SynologySettings {
+    /// Profile name, used as command target ("/ssh backup on")
+    pub name: String,
+    pub base_url: String,
+    pub username: String,
+    // Empty if the credentials come from OpenBao
+    pub password: String,
+    pub force_ipv4: bool,
+    pub idle_timeout: Duration,
+    pub tls: TlsConfig,
+    // KV v2 mount and path of the credentials in OpenBao
+    pub credentials_secret: Option<(String, String)>,
+    /// Wake-on-LAN target for /wake
+    pub wol: Option<WolTarget>,
+}
+
+/// OpenBao cluster, unseal page URL and the optional token login
+pub struct BaoSettings {
+    pub addrs: String,
+    pub web_url: String,
+    pub auth: Option<BaoAuth>,
+    pub tls: TlsConfig,
+}
+
+/// The validated configuration, built once at startup
+pub struct Config {
+    /// Config file the settings came from, if any
+    pub file: Option<PathBuf>,
+    pub telegram_bot_token: String,
+    /// NAS profiles; the first one is the default target of commands
+    pub nas: Vec<SynologySettings>,
+    pub bao: Option<BaoSettings>,
+    pub access: AccessControl,
+    pub monitor: MonitorConfig,
+    pub unseal_web: Option<UnsealWebConfig>,
+    pub state_dir: PathBuf,
+}
+
+impl Config {
+    pub fn from_settings(settings: &Settings) -> Result<Self, ConfigError> {
+        let mut errors = Vec::new();
+
+        let telegram_bot_token = settings.secret("STB_TELEGRAM_BOT_TOKEN")
+            .and_then(|token| token.ok_or_else(|| "STB_TELEGRAM_BOT_TOKEN (or STB_TELEGRAM_BOT_TOKEN_FILE) is not set".to_string()))
+            .map_err(|e| errors.push(e))
+            .ok();
+        let bao = bao_settings(settings).map_err(|e| errors.extend(e)).ok();
+        let has_bao_auth = bao.as_ref().is_some_and(|bao| bao.as_ref().is_some_and(|b| b.auth.is_some()));
+        let nas = nas_settings(settings, has_bao_auth).map_err(|e| errors.extend(e)).ok();
+        let access = AccessControl::from_settings(settings).map_err(|e| errors.extend(e)).ok();
+        let monitor = MonitorConfig::from_settings(settings).map_err(|e| errors.extend(e)).ok();
+        let unseal_web = UnsealWebConfig::from_settings(settings).map_err(|e| errors.push(e)).ok();
+        let state_dir = PathBuf::from(settings.get("STB_STATE_DIR").unwrap_or_else(|| DEFAULT_STATE_DIR.to_string()));
+
+        match (telegram_bot_token, nas, bao, access, monitor, unseal_web) {
+            (Some(telegram_bot_token), Some(nas), Some(bao), Some(access), Some(monitor), Some(unseal_web)) if errors.is_empty() => Ok(Config {
+                file: settings.file().map(Path::to_path_buf),
+                telegram_bot_token,
+                nas,
+                bao,
+                access,
+                monitor,
+                unseal_web,
+                state_dir,
+            }),
+            _ => Err(ConfigError(errors)),
+        }
+    }
+}
+
+// Variable names of one NAS profile. The first profile keeps the names from before profiles
+// existed (`STB_SYNOLOGY_*`, `STB_FORCE_IPV4`), the others use `STB_NAS_<NAME>_*`.
+struct NasVars {
+    prefix: String,
+    base_url: String,
+    force_ipv4: String,
+}
+
+impl NasVars {
+    fn primary() -> Self {
+        NasVars {
+            prefix: "STB_SYNOLOGY".to_string(),
+            base_url: "STB_SYNOLOGY_NAS_BASE_URL".to_string(),
+            force_ipv4: "STB_FORCE_IPV4".to_string(),
+        }
+    }
+
+    fn profile(name: &str) -> Self {
+        let prefix = env_name(&format!("nas.{}", name));
+        NasVars {
+            base_url: format!("{}_BASE_URL", prefix),
+            force_ipv4: format!("{}_FORCE_IPV4", prefix),
+            prefix,
+        }
+    }
+
+    fn var(&self, name: &str) -> String {
+        format!("{}_{}", self.prefix, name)
+    }
+}
+
+// NAS names are used in commands ("/ssh backup on") and in button data
+fn check_nas_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_') {
+        return Err(format!("NAS name '{}' may only contain lowercase letters, digits, '-' and '_'", name));
+    }
+    if matches!(name, "on" | "off" | "enable" | "disable") || find_service(name).is_some() {
+        return Err(format!("NAS name '{}' is reserved, it is a command argument", name));
+    }
+    Ok(())
+}
+
+// The first profile from STB_SYNOLOGY_*, then one per name in STB_NAS_PROFILES
+fn nas_settings(settings: &Settings, has_bao_auth: bool) -> Result<Vec<SynologySettings>, Vec<String>> {
+    let mut errors = Vec::new();
+    let mut profiles = vec![(settings.get("STB_SYNOLOGY_NAME").unwrap_or_else(|| DEFAULT_NAS_NAME.to_string()), NasVars::primary())];
+    for name in settings.get("STB_NAS_PROFILES").iter().flat_map(|names| names.split(',')) {
+        let name = name.trim();
+        if !name.is_empty() {
+            profiles.push((name.to_string(), NasVars::profile(name)));
+        }
+    }
+
+    let mut nas = Vec::new();
+    for (name, vars) in profiles {
+        if let Err(e) = check_nas_name(&name) {
+            errors.push(e);
+            continue;
+        }
+        if nas.iter().any(|n: &SynologySettings| n.name == name) {
+            errors.push(format!("NAS name '{}' is used twice", name));
+            continue;
+        }
+        match synology_settings(settings, name, &vars, has_bao_auth) {
+            Ok(settings) => nas.push(settings),
+            Err(e) => errors.extend(e),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(nas)
+}
+
+fn synology_settings(settings: &Settings, name: String, vars: &NasVars, has_bao_auth: bool) -> Result<SynologySettings, Vec<String>> {
+    let mut errors = Vec::new();
+
+    let base_url = match settings.get(&vars.base_url) {
+        Some(url) => parse_url(&vars.base_url, &url).map_err(|e| errors.push(e)).unwrap_or_default(),
+        None => {
+            errors.push(format!("{} is not set", vars.base_url));
+            String::new()
+        }
+    };
+
+    // With <PREFIX>_BAO_KV_PATH the password (and optionally the username) come from OpenBao
+    let credentials_secret = settings.get(&vars.var("BAO_KV_PATH")).map(|path| {
+        let mount = settings.get(&vars.var("BAO_KV_MOUNT")).unwrap_or_else(|| "secret".to_string());
+        (mount, path)
+    });
+    let username = settings.get(&vars.var("USERNAME")).unwrap_or_default();
+    let password = match &credentials_secret {
+        Some(_) if !has_bao_auth => {
+            errors.push(format!("{} needs OpenBao (STB_BAO_ADDR, STB_UNSEAL_WEB_URL) with a token (STB_BAO_TOKEN_FILE or STB_BAO_ROLE_ID/STB_BAO_SECRET_ID)", vars.var("BAO_KV_PATH")));
+            String::new()
+        },
+        Some(_) => String::new(),
+        None => {
+            if username.is_empty() {
+                errors.push(format!("{} is not set", vars.var("USERNAME")));
+            }
+            let password_var = vars.var("PASSWORD");
+            match settings.secret(&password_var) {
+                Ok(Some(password)) => password,
+                Ok(None) => {
+                    errors.push(format!("{} (or {}_FILE, or {}) is not set", password_var, password_var, vars.var("BAO_KV_PATH")));
+                    String::new()
+                },
+                Err(e) => {
+                    errors.push(e);
+                    String::new()
+                }
+            }
+        }
+    };
+
+    let force_ipv4 = match settings.get(&vars.force_ipv4) {
+        Some(value) => parse_bool(&vars.force_ipv4, &value).map_err(|e| errors.push(e)).unwrap_or_default(),
+        None => false,
+    };
+    let idle_timeout_var = vars.var("IDLE_TIMEOUT");
+    let idle_timeout = match settings.get(&idle_timeout_var) {
+        Some(value) => parse_duration(&value).unwrap_or_else(|| {
+            errors.push(format!("{} '{}' is not a valid duration (e.g. 10m)", idle_timeout_var, value));
+            DEFAULT_SESSION_IDLE_TIMEOUT
+        }),
+        None => DEFAULT_SESSION_IDLE_TIMEOUT,
+    };
+    let tls = TlsConfig::from_settings(settings, &vars.prefix).map_err(|e| errors.push(e.to_string())).unwrap_or_default();
+    let wol_mac_var = vars.var("WOL_MAC");
+    let wol_broadcast = settings.get(&vars.var("WOL_BROADCAST"));
+    let wol = match settings.get(&wol_mac_var) {
+        Some(mac) => WolTarget::parse(&mac, wol_broadcast.as_deref())
+            .map_err(|e| errors.push(format!("{}: {}", wol_mac_var, e)))
+            .ok(),
+        None => {
+            if wol_broadcast.is_some() {
+                errors.push(format!("{} needs {}", vars.var("WOL_BROADCAST"), wol_mac_var));
+            }
+            None
+        }
+    };
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(SynologySettings { name, base_url, username, password, force_ipv4, idle_timeout, tls, credentials_secret, wol })
+}
+
+fn bao_settings(settings: &Settings) -> Result<Option<BaoSettings>, Vec<String>> {
+    let (addrs, web_url) = match (settings.get("STB_BAO_ADDR"), settings.get("STB_UNSEAL_WEB_URL")) {
+        (Some(addrs), Some(web_url)) => (addrs, web_url),
+        (None, None) => return Ok(None),
+        _ => return Err(vec!["STB_BAO_ADDR and STB_UNSEAL_WEB_URL must be set together".to_string()]),
+    };
+
+    let mut errors = Vec::new();
+    for addr in addrs.split(',').map(str::trim).filter(|a| !a.is_empty()) {
+        if let Err(e) = parse_url("STB_BAO_ADDR", addr) {
+            errors.push(e);
+        }
+    }
+    let web_url = parse_url("STB_UNSEAL_WEB_URL", &web_url).map_err(|e| errors.push(e)).unwrap_or_default();
+    let auth = BaoAuth::from_settings(settings).map_err(|e| errors.push(e)).unwrap_or_default();
+    let tls = TlsConfig::from_settings(settings, "STB_BAO").map_err(|e| errors.push(e.to_string())).unwrap_or_default();
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(Some(BaoSettings { addrs, web_url, auth, tls }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(file: &str, content: &str, env: &[(&str, &str)]) -> Settings {
+        let values = parse_file(Path::new(file), content).unwrap();
+        let env = env.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        Settings::new(Some(PathBuf::from(file)), values, env)
+    }
+
+    const TOML: &str = r#"
+state_dir = "/var/lib/stb"
+
+[telegram]
+bot_token = "123:abc"
+
+[synology]
+nas_base_url = "https://nas.local:5001"
+username = "bot"
+password = "secret"
+idle_timeout = "15m"
+
+[allowed]
+users = ["111:admin", "222:operator"]
+chat_id = -1001234
+
+[monitor]
+interval = "off"
+disk_temp = 60
+"#;
+
+    #[test]
+    fn toml_and_yaml_map_to_env_names() {
+        let yaml = r#"
+state_dir: /var/lib/stb
+telegram:
+  bot_token: "123:abc"
+synology:
+  nas_base_url: https://nas.local:5001
+  username: bot
+  password: secret
+  idle_timeout: 15m
+allowed:
+  users: ["111:admin", "222:operator"]
+  chat_id: -1001234
+monitor:
+  interval: "off"
+  disk_temp: 60
+"#;
+        let toml = settings("config.toml", TOML, &[]);
+        let yaml = settings("config.yaml", yaml, &[]);
+        assert_eq!(toml.values, yaml.values);
+        assert_eq!(toml.get("STB_ALLOWED_USERS").as_deref(), Some("111:admin,222:operator"));
+        assert_eq!(toml.get("STB_MONITOR_DISK_TEMP").as_deref(), Some("60"));
+        assert!(parse_file(Path::new("config.ini"), "").is_err());
+    }
+
+    #[test]
+    fn environment_overrides_file() {
+        let settings = settings("config.toml", TOML, &[("STB_SYNOLOGY_USERNAME", "admin"), ("STB_STATE_DIR", " ")]);
+        assert_eq!(settings.get("STB_SYNOLOGY_USERNAME").as_deref(), Some("admin"));
+        // Empty variables (as defined by docker compose) do not hide the file
+        assert_eq!(settings.get("STB_STATE_DIR").as_deref(), Some("/var/lib/stb"));
+    }
+
+    #[test]
+    fn valid_config_is_typed() {
+        let settings = settings("config.toml", &format!("{}\n[extra]\ntypo = 1\n", TOML), &[]);
+        let config = Config::from_settings(&settings).unwrap();
+        assert_eq!(config.nas[0].base_url, "https://nas.local:5001");
+        assert_eq!(config.nas[0].idle_timeout, Duration::from_secs(900));
+        assert_eq!(config.monitor.interval, None);
+        assert_eq!(config.monitor.disk_temperature, Some(60));
+        assert_eq!(config.state_dir, PathBuf::from("/var/lib/stb"));
+        assert!(config.bao.is_none());
+        assert_eq!(settings.unused_keys(), vec!["extra.typo".to_string()]);
+    }
+
+    #[test]
+    fn reports_all_problems() {
+        let settings = settings("config.toml", TOML, &[
+            ("STB_SYNOLOGY_NAS_BASE_URL", "nas.local:5001"),
+            ("STB_ALLOWED_CHAT_ID", "my-chat"),
+            ("STB_BAO_ADDR", "https://bao:8200"),
+        ]);
+        let errors = match Config::from_settings(&settings) {
+            Err(ConfigError(errors)) => errors,
+            Ok(_) => panic!("config must be rejected"),
+        };
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+        assert!(errors.iter().any(|e| e.starts_with("STB_SYNOLOGY_NAS_BASE_URL")));
+        assert!(errors.iter().any(|e| e.contains("'my-chat' is not a numeric chat ID")));
+        assert!(errors.iter().any(|e| e.contains("must be set together")));
+    }
+
+    #[test]
+    fn nas_profiles_have_their_own_settings() {
+        let profiles = r#"
+[nas]
+profiles = ["backup"]
+
+[nas.backup]
+base_url = "https://10.0.0.7:5001"
+username = "backup-bot"
+password = "other"
+force_ipv4 = true
+wol_mac = "00:11:32:aa:bb:cc"
+wol_broadcast = "10.0.0.255"
+"#;
+        let settings = settings("config.toml", &format!("{}{}", TOML, profiles), &[("STB_SYNOLOGY_NAME", "office")]);
+        let config = Config::from_settings(&settings).unwrap();
+        let names: Vec<&str> = config.nas.iter().map(|n| n.name.as_str()).collect();
+        assert_eq!(names, vec!["office", "backup"]);
+        assert_eq!(config.nas[1].base_url, "https://10.0.0.7:5001");
+        assert_eq!(config.nas[1].username, "backup-bot");
+        assert!(config.nas[1].force_ipv4 && !config.nas[0].force_ipv4);
+        assert_eq!(config.nas[1].wol.as_ref().unwrap().broadcast, "10.0.0.255:9".parse().unwrap());
+        assert!(config.nas[0].wol.is_none());
+        assert!(settings.unused_keys().is_empty());
+
+        let settings = self::settings("config.toml", TOML, &[("STB_NAS_PROFILES", "Backup,smb")]);
+        let errors = match Config::from_settings(&settings) {
+            Err(ConfigError(errors)) => errors,
+            Ok(_) => panic!("config must be rejected"),
+        };
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+    }
+}
@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use teloxide::types::ChatId;
+
+use crate::bao::random_session_id;
+
+struct Pending<T> {
+    item: T,
+    chat_id: ChatId,
+    expires_at: Instant,
+}
+
+/// Actions waiting for their confirmation button (/reboot, /shutdown, /seal), by nonce. The
+/// nonce is all the buttons carry, so a button from an old message does nothing once its
+/// confirmation has expired or was used.
+pub struct Confirmations<T> {
+    pending: HashMap<String, Pending<T>>,
+}
+
+impl<T> Default for Confirmations<T> {
+    fn default() -> Self {
+        Confirmations { pending: HashMap::new() }
+    }
+}
+
+impl<T> Confirmations<T> {
+    /// Register a confirmation and return its nonce.
+    pub fn create(&mut self, item: T, chat_id: ChatId, timeout: Duration) -> String {
+        self.pending.retain(|_, p| p.expires_at > Instant::now());
+        // 8 hex characters are plenty for a minute and keep the button data short
+        let nonce = random_session_id()[..8].to_string();
+        self.pending.insert(nonce.clone(), Pending {
+            item,
+            chat_id,
+            expires_at: Instant::now() + timeout,
+        });
+        nonce
+    }
+
+    /// Use up a confirmation. `None` if it is unknown, expired or from another chat; a press
+    /// from another chat (e.g. a forwarded message) leaves it valid for its own chat.
+    pub fn take(&mut self, nonce: &str, chat_id: ChatId) -> Option<T> {
+        let pending = self.pending.get(nonce)?;
+        if pending.chat_id != chat_id || pending.expires_at <= Instant::now() {
+            return None;
+        }
+        self.pending.remove(nonce).map(|p| p.item)
+    }
+
+    /// Drop a confirmation after its timeout. `true` if it was never used, so its buttons are
+    /// still on screen.
+    pub fn discard(&mut self, nonce: &str) -> bool {
+        self.pending.remove(nonce).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn confirmation_is_used_once_and_only_in_its_chat() {
+        let mut confirmations = Confirmations::default();
+        let nonce = confirmations.create("reboot", ChatId(1), Duration::from_secs(60));
+        assert_eq!(nonce.len(), 8);
+        assert!(confirmations.take(&nonce, ChatId(2)).is_none());
+        assert_eq!(confirmations.take(&nonce, ChatId(1)), Some("reboot"));
+        assert!(confirmations.take(&nonce, ChatId(1)).is_none());
+        assert!(!confirmations.discard(&nonce));
+
+        let stale = confirmations.create("seal", ChatId(1), Duration::ZERO);
+        assert!(confirmations.take(&stale, ChatId(1)).is_none());
+        assert!(confirmations.discard(&stale));
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::{HashMap, VecDeque};
+use teloxide::types::{ChatId, InlineKeyboardButton, InlineKeyboardMarkup};
+use tokio::sync::Mutex;
+
+use crate::bao::random_session_id;
+use crate::synology::{FileInfo, FileListData};
+use crate::{format_bytes, Nas, CALLBACK_FILES};
+
+/// Entries per page of a listing
+pub const FILES_PAGE_SIZE: u32 = 20;
+// Tokens kept for the buttons of older listings; the oldest are dropped first
+const MAX_FILE_TOKENS: usize = 1000;
+// Names are cut in the listing and on buttons, so a page stays within Telegram's limits
+const MAX_NAME_CHARS: usize = 60;
+const MAX_BUTTON_CHARS: usize = 28;
+
+/// A folder of one NAS; an empty path stands for the list of shared folders
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BrowseTarget {
+    pub nas: String,
+    pub path: String,
+}
+
+/// Folder paths by short token. Button data is limited to 64 bytes, so the buttons carry the
+/// token ("fs:1f2e3d4c:20") and the path stays in the bot. Tokens are lost on restart, the
+/// buttons of older listings then ask for a new /files.
+#[derive(Default)]
+pub struct FileTokens {
+    targets: HashMap<String, BrowseTarget>,
+    tokens: HashMap<BrowseTarget, String>,
+    // Insertion order, for dropping the oldest tokens
+    order: VecDeque<String>,
+}
+
+impl FileTokens {
+    /// Token for a folder, the same one for every listing that links to it.
+    pub fn token_for(&mut self, nas: &str, path: &str) -> String {
+        let target = BrowseTarget { nas: nas.to_string(), path: path.to_string() };
+        if let Some(token) = self.tokens.get(&target) {
+            return token.clone();
+        }
+        let token = loop {
+            let token = random_session_id()[..8].to_string();
+            if !self.targets.contains_key(&token) {
+                break token;
+            }
+        };
+        while self.order.len() >= MAX_FILE_TOKENS {
+            if let Some(old) = self.order.pop_front() && let Some(target) = self.targets.remove(&old) {
+                self.tokens.remove(&target);
+            }
+        }
+        self.targets.insert(token.clone(), target.clone());
+        self.tokens.insert(target, token.clone());
+        self.order.push_back(token.clone());
+        token
+    }
+
+    pub fn resolve(&self, token: &str) -> Option<BrowseTarget> {
+        self.targets.get(token).cloned()
+    }
+}
+
+/// Button data of a page: "fs:<token>:<offset>"
+fn page_callback(token: &str, offset: u32) -> String {
+    format!("{}{}:{}", CALLBACK_FILES, token, offset)
+}
+
+/// Token and offset from the data of a page button (without the prefix)
+pub fn parse_page_callback(data: &str) -> Option<(&str, u32)> {
+    let (token, offset) = data.split_once(':')?;
+    Some((token, offset.parse().ok()?))
+}
+
+// Folder above `path`; the shared folders above a share
+fn parent_path(path: &str) -> &str {
+    path.rsplit_once('/').map_or("", |(parent, _)| parent)
+}
+
+// Cut long names, keeping the start
+fn shorten(name: &str, max_chars: usize) -> String {
+    if name.chars().count() <= max_chars {
+        name.to_string()
+    } else {
+        format!("{}…", name.chars().take(max_chars - 1).collect::<String>())
+    }
+}
+
+/// UTC date and time of a Unix timestamp, e.g. "2024-05-01 10:22"
+pub fn format_timestamp(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let (hour, minute) = ((secs % 86_400) / 3600, (secs % 3600) / 60);
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02} {:02}:{:02}", year, month, day, hour, minute)
+}
+
+fn format_entry(file: &FileInfo) -> String {
+    let mut line = if file.isdir {
+        format!("📁 {}/", shorten(&file.name, MAX_NAME_CHARS))
+    } else {
+        format!("📄 {}", shorten(&file.name, MAX_NAME_CHARS))
+    };
+    if !file.isdir && let Some(size) = file.additional.size {
+        line.push_str(&format!(" · {}", format_bytes(size)));
+    }
+    if let Some(time) = &file.additional.time {
+        line.push_str(&format!(" · {}", format_timestamp(time.mtime)));
+    }
+    line
+}
+
+/// Listing of one page: folder, position and one line per entry with size and modification time
+pub fn format_listing(path: &str, data: &FileListData) -> String {
+    let title = if path.is_empty() { "Shared folders" } else { path };
+    let mut text = if data.files.is_empty() {
+        format!("📂 {}\n\n(empty)", title)
+    } else {
+        format!(
+            "📂 {} ({}-{} of {})\n\n",
+            title,
+            data.offset + 1,
+            data.offset + data.files.len() as i32,
+            data.total
+        )
+    };
+    let lines: Vec<String> = data.files.iter().map(format_entry).collect();
+    text.push_str(&lines.join("\n"));
+    text.push_str("\n\nTimes in UTC.");
+    text
+}
+
+/// One button per subfolder, then up and the page controls
+pub fn create_files_menu(tokens: &mut FileTokens, nas: &str, path: &str, data: &FileListData) -> InlineKeyboardMarkup {
+    let mut keyboard: Vec<Vec<InlineKeyboardButton>> = data
+        .files
+        .iter()
+        .filter(|file| file.isdir)
+        .map(|dir| {
+            let token = tokens.token_for(nas, &dir.path);
+            InlineKeyboardButton::callback(format!("📁 {}", shorten(&dir.name, MAX_BUTTON_CHARS)), page_callback(&token, 0))
+        })
+        .collect::<Vec<_>>()
+        .chunks(2)
+        .map(|row| row.to_vec())
+        .collect();
+
+    let token = tokens.token_for(nas, path);
+    let offset = data.offset.max(0) as u32;
+    let mut controls = Vec::new();
+    if offset > 0 {
+        controls.push(InlineKeyboardButton::callback("◀️ Prev", page_callback(&token, offset.saturating_sub(FILES_PAGE_SIZE))));
+    }
+    if !path.is_empty() {
+        let parent = tokens.token_for(nas, parent_path(path));
+        controls.push(InlineKeyboardButton::callback("⬆️ Up", page_callback(&parent, 0)));
+    }
+    if (offset as usize + data.files.len()) < data.total.max(0) as usize {
+        controls.push(InlineKeyboardButton::callback("Next ▶️", page_callback(&token, offset + data.files.len() as u32)));
+    }
+    if !controls.is_empty() {
+        keyboard.push(controls);
+    }
+    InlineKeyboardMarkup::new(keyboard)
+}
+
+/// Load one page of a folder (or of the shared folders) and build its message
+pub async fn browse(nas: &Nas, tokens: &Mutex<FileTokens>, path: &str, offset: u32, chat_id: ChatId) -> Result<(String, InlineKeyboardMarkup), String> {
+    let data = {
+        let mut config = nas.synology.lock().await;
+        let client = config.logged_in_client(chat_id).await?;
+        let result = if path.is_empty() {
+            client.list_shares(offset, FILES_PAGE_SIZE).await
+        } else {
+            client.list_folder(path, offset, FILES_PAGE_SIZE).await
+        };
+        result.map_err(|e| format!("Failed to list {}: {}", if path.is_empty() { "the shared folders" } else { path }, e))?
+    };
+    let menu = create_files_menu(&mut *tokens.lock().await, &nas.name, path, &data);
+    Ok((format!("{}{}", nas.prefix, format_listing(path, &data)), menu))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synology::{FileAdditional, FileTime};
+
+    fn entry(name: &str, isdir: bool) -> FileInfo {
+        FileInfo {
+            name: name.to_string(),
+            path: format!("/photo/{}", name),
+            isdir,
+            additional: FileAdditional {
+                size: Some(2048),
+                time: Some(FileTime { ctime: 0, mtime: 1_714_558_920, atime: 0 }),
+            },
+        }
+    }
+
+    #[test]
+    fn tokens_are_short_and_reused() {
+        let mut tokens = FileTokens::default();
+        let long_path = format!("/photo/{}", "a".repeat(200));
+        let token = tokens.token_for("main", &long_path);
+        assert!(page_callback(&token, 1_000_000).len() <= 64);
+        assert_eq!(tokens.token_for("main", &long_path), token);
+        assert_ne!(tokens.token_for("backup", &long_path), token);
+        assert_eq!(tokens.resolve(&token).unwrap().path, long_path);
+        assert_eq!(parse_page_callback(&format!("{}:40", token)), Some((token.as_str(), 40)));
+        assert!(tokens.resolve("unknown").is_none());
+    }
+
+    #[test]
+    fn builds_page_controls_from_offset_and_total() {
+        let mut tokens = FileTokens::default();
+        let data = FileListData { files: vec![entry("2024", true), entry("a.jpg", false)], total: 42, offset: 20 };
+        let menu = create_files_menu(&mut tokens, "main", "/photo", &data);
+        let labels: Vec<&str> = menu.inline_keyboard.iter().flatten().map(|b| b.text.as_str()).collect();
+        assert_eq!(labels, vec!["📁 2024", "◀️ Prev", "⬆️ Up", "Next ▶️"]);
+
+        let text = format_listing("/photo", &data);
+        assert!(text.starts_with("📂 /photo (21-22 of 42)"));
+        assert!(text.contains("📄 a.jpg · 2.0 KB · 2024-05-01 10:22"));
+        assert_eq!(parent_path("/photo/2024"), "/photo");
+        assert_eq!(parent_path("/photo"), "");
+    }
+
+    #[test]
+    fn formats_timestamps_in_utc() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29 00:00");
+        assert_eq!(format_timestamp(1_714_558_920), "2024-05-01 10:22");
+    }
+}
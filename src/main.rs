@@ -1,47 +1,79 @@
+use std::collections::HashMap;
 use std::error::Error;
 use std::sync::Arc;
-use std::time::{Duration, Instant};
-use teloxide::{prelude::*, utils::command::BotCommands};
-use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup, CallbackQuery, InlineQuery, InlineQueryResult, InlineQueryResultArticle, InputMessageContent, InputMessageContentText, MenuButton};
+use std::time::Duration;
+use teloxide::{net::Download, prelude::*, utils::command::BotCommands};
+use teloxide::{ApiError, RequestError};
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup, CallbackQuery, InlineQuery, InlineQueryResult, InlineQueryResultArticle, InputMessageContent, InputMessageContentText, Document, Me, MenuButton, MessageKind, UpdateKind};
 use tokio::sync::Mutex;
 use log::{error, info, warn};
 use local_ip_address::local_ip;
 
 mod synology;
-use synology::SynologyClient;
+use synology::{find_service, ServiceDef, StorageInfo, SynologyClient, SynologyClientError, SystemInfo, SystemUtilization, SERVICES, SSH_SERVICE};
+
+mod state;
+use state::StateStore;
+
+mod ssh_timer;
+use ssh_timer::{format_duration, parse_duration, NasRef, SshAutoDisable};
+
+mod monitor;
+
+mod auth;
+use auth::{AccessControl, Authorization, Role};
+
+mod secrets;
+
+mod config;
+use config::{BaoSettings, Config, Settings, SynologySettings};
+
+mod tls;
+use tls::TlsConfig;
 
 mod bao;
-use bao::{decrypt_ciphertext, generate_ephemeral_key, random_session_id, BaoClient};
+use bao::{AutopilotState, BaoCluster, BaoError, NodeStatus, TokenInfo, AGE_ARMOR_HEADER};
+
+mod unseal;
+use unseal::{UnsealSession, UnsealSessions, UNSEAL_SESSION_TIMEOUT};
+
+mod web;
+
+mod wol;
+use wol::WolTarget;
+
+mod confirm;
+use confirm::Confirmations;
 
-// OpenBao unseal configuration (optional feature; enabled when both env vars are set)
+mod power;
+use power::{create_power_confirm_menu, PendingPowerAction, PowerAction, PowerConfirmations, POWER_CONFIRM_TIMEOUT};
+
+mod files;
+use files::{parse_page_callback, FileTokens};
+
+// OpenBao unseal configuration (optional feature; enabled when both settings are given)
 struct BaoConfig {
-    client: BaoClient,
+    cluster: BaoCluster,
     web_url: String,
+    // Open /seal confirmations, by the nonce in their buttons
+    seal_confirmations: Mutex<Confirmations<()>>,
 }
 
 impl BaoConfig {
-    fn from_env() -> Option<Self> {
-        // Note: compose always defines these vars (possibly empty) — treat empty as unset.
-        let addr = std::env::var("STB_BAO_ADDR").ok().filter(|v| !v.is_empty())?;
-        let web_url = std::env::var("STB_UNSEAL_WEB_URL").ok().filter(|v| !v.is_empty())?;
-        info!("OpenBao unseal support enabled (addr: {}, web: {})", addr, web_url);
-        Some(BaoConfig {
-            client: BaoClient::new(&addr),
-            web_url: web_url.trim_end_matches('/').to_string(),
-        })
+    fn new(settings: &BaoSettings) -> Self {
+        info!("OpenBao unseal support enabled (nodes: {}, web: {})", settings.addrs, settings.web_url);
+        // Optional token for /seal, /bao_stepdown and the details in /bao_health
+        if settings.auth.is_none() {
+            info!("No OpenBao token configured; /seal, /bao_stepdown and the Raft details of /bao_health are unavailable");
+        }
+        BaoConfig {
+            cluster: BaoCluster::new(&settings.addrs, &settings.tls, settings.auth.clone()),
+            web_url: settings.web_url.clone(),
+            seal_confirmations: Mutex::new(Confirmations::default()),
+        }
     }
 }
 
-// Pending unseal challenge: ephemeral keypair + session reference (lives only in RAM)
-struct UnsealSession {
-    chat_id: ChatId,
-    session_id: String,
-    identity: age::x25519::Identity,
-    since: Instant,
-}
-
-const UNSEAL_SESSION_TIMEOUT: Duration = Duration::from_secs(300);
-
 // Structure to hold the Synology client configuration
 struct SynologyConfig {
     client: Option<SynologyClient>,
@@ -49,60 +81,133 @@ struct SynologyConfig {
     username: String,
     password: String,
     force_ipv4: bool,
+    // DSM device token from an earlier 2-step verification
+    device_id: Option<String>,
+    // The DSM session is logged out after this long without requests
+    session_idle_timeout: Duration,
+    // CA bundle, client certificate, server name and fingerprint pinning for DSM
+    tls: TlsConfig,
+    // KV v2 mount and path of the DSM credentials in OpenBao, instead of the environment
+    credentials_secret: Option<(String, String)>,
+    bao_config: Arc<Option<BaoConfig>>,
 }
 
 // Callback data for menu buttons
 const CALLBACK_SSH_MENU: &str = "ssh_menu";
 const CALLBACK_SSH_ON: &str = "ssh_on";
 const CALLBACK_SSH_OFF: &str = "ssh_off";
+// Timed enable, followed by the number of minutes: "ssh_on_for:30"
+const CALLBACK_SSH_ON_FOR: &str = "ssh_on_for:";
+const CALLBACK_SSH_EXTEND: &str = "ssh_extend";
+const CALLBACK_SERVICES: &str = "services";
+const CALLBACK_STORAGE: &str = "storage";
+const CALLBACK_SYSTEM: &str = "system";
+// System overview of all NAS
+const CALLBACK_SYSTEM_ALL: &str = "system_all";
+const CALLBACK_UNSEAL: &str = "unseal";
+// Key holder joins the unseal session, followed by the session ID: "unseal_join:<id>"
+const CALLBACK_UNSEAL_JOIN: &str = "unseal_join:";
+// Confirmation of /seal, followed by the nonce: "seal_ok:1f2e3d4c"
+const CALLBACK_SEAL_CONFIRM: &str = "seal_ok:";
+const CALLBACK_SEAL_CANCEL: &str = "seal_cancel:";
+// How long the confirmation buttons of /seal are valid
+const SEAL_CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
+// Followed by the service name: "svc_on:smb"
+const CALLBACK_SERVICE_ON: &str = "svc_on:";
+const CALLBACK_SERVICE_OFF: &str = "svc_off:";
 const CALLBACK_SETTINGS: &str = "settings";
+// Confirmation of /reboot and /shutdown, followed by the nonce: "power_ok:1f2e3d4c"
+const CALLBACK_POWER_CONFIRM: &str = "power_ok:";
+const CALLBACK_POWER_CANCEL: &str = "power_cancel:";
+// File Station page, followed by the folder token and the offset: "fs:1f2e3d4c:20"
+const CALLBACK_FILES: &str = "fs:";
+// NAS selector in the main menu, followed by the NAS name: "nas_select:backup"
+const CALLBACK_NAS_SELECT: &str = "nas_select:";
 const CALLBACK_BACK: &str = "back";
 
 impl SynologyConfig {
-    fn new() -> Self {
-        let nas_base_url = std::env::var("STB_SYNOLOGY_NAS_BASE_URL").unwrap();
-        let username = std::env::var("STB_SYNOLOGY_USERNAME").unwrap_or_else(|_| {
-            warn!("STB_SYNOLOGY_USERNAME environment variable not set");
-            String::new()
-        });
-        let password = std::env::var("STB_SYNOLOGY_PASSWORD").unwrap_or_else(|_| {
-            warn!("STB_SYNOLOGY_PASSWORD environment variable not set");
-            String::new()
-        });
-
-        // Check if IPv4 should be forced
-        let force_ipv4 = std::env::var("STB_FORCE_IPV4")
-            .map(|v| v.to_lowercase() == "true" || v == "1")
-            .unwrap_or(false);
-
-        if force_ipv4 {
+    fn new(settings: &SynologySettings, bao_config: Arc<Option<BaoConfig>>) -> Self {
+        if settings.force_ipv4 {
             info!("IPv4 will be forced for Synology API requests");
         }
+        if let Some((mount, path)) = &settings.credentials_secret {
+            info!("Synology credentials are read from OpenBao KV {}/{}", mount, path);
+        }
 
-        info!("Initializing Synology configuration with base URL: {}", nas_base_url);
+        info!("Initializing Synology configuration {} with base URL: {}", settings.name, settings.base_url);
 
         SynologyConfig {
             client: None,
-            nas_base_url,
-            username,
-            password,
-            force_ipv4,
+            nas_base_url: settings.base_url.clone(),
+            username: settings.username.clone(),
+            password: settings.password.clone(),
+            force_ipv4: settings.force_ipv4,
+            device_id: None,
+            session_idle_timeout: settings.idle_timeout,
+            tls: settings.tls.clone(),
+            credentials_secret: settings.credentials_secret.clone(),
+            bao_config,
+        }
+    }
+
+    // Read the DSM credentials from OpenBao and keep them in memory. A changed password ends
+    // the current DSM session, so the next command logs in with the new one.
+    async fn load_credentials(&mut self) -> Result<(), String> {
+        let (Some((mount, path)), Some(bao)) = (&self.credentials_secret, self.bao_config.as_ref()) else {
+            return Ok(());
+        };
+        if bao.cluster.seal_status().await.is_ok_and(|status| status.sealed) {
+            return Err("credentials unavailable: vault sealed".to_string());
+        }
+        let mut fields = bao.cluster.read_kv(mount, path).await
+            .map_err(|e| format!("credentials unavailable: {}", e))?;
+        let password = fields.remove("password")
+            .ok_or_else(|| format!("credentials unavailable: no 'password' in {}/{}", mount, path))?;
+        // The username may also stay in STB_SYNOLOGY_USERNAME
+        let username = fields.remove("username").unwrap_or_else(|| self.username.clone());
+
+        if username != self.username || password != self.password {
+            if let Some(mut client) = self.client.take()
+                && let Err(e) = client.logout().await
+            {
+                warn!("Failed to logout from Synology NAS after a credential change: {}", e);
+            }
+            info!("Synology credentials loaded from OpenBao KV {}/{}", mount, path);
+        }
+        self.username = username;
+        self.password = password;
+        Ok(())
+    }
+
+    // Fetch the credentials again, e.g. after OpenBao was unsealed; only with an OpenBao secret
+    async fn reload_credentials(&mut self) {
+        if self.credentials_secret.is_some() && let Err(e) = self.load_credentials().await {
+            warn!("Failed to reload Synology credentials: {}", e);
         }
     }
 
     fn create_client(&mut self) {
-        self.client = Some(SynologyClient::new(
+        let mut client = SynologyClient::new(
             &self.nas_base_url, 
             &self.username, 
             &self.password,
-            self.force_ipv4
-        ));
+            self.force_ipv4,
+            &self.tls
+        );
+        client.set_device_id(self.device_id.clone());
+        self.client = Some(client);
     }
 
-    // Automatically login if needed
-    async fn ensure_logged_in(&mut self) -> Result<bool, reqwest::Error> {
+    // Automatically login if needed. `chat_id` is the chat the following requests are made
+    // for; a 2-step verification prompt they run into can only be answered from there.
+    async fn ensure_logged_in(&mut self, chat_id: Option<ChatId>) -> Result<bool, Box<dyn Error + Send + Sync>> {
         // Create client if it doesn't exist
         if self.client.is_none() {
+            // Credentials from OpenBao are fetched on first use, e.g. if it was sealed at startup
+            if self.credentials_secret.is_some() && self.password.is_empty() {
+                self.load_credentials().await?;
+            }
+
             // Check if username and password are set
             if self.username.is_empty() || self.password.is_empty() {
                 warn!("Cannot login: Synology username or password not set in environment variables");
@@ -112,47 +217,577 @@ impl SynologyConfig {
             self.create_client();
         }
 
+        if let Some(client) = self.client.as_mut() {
+            client.set_requester(chat_id.map(|chat_id| chat_id.0));
+        }
         // The client will automatically attempt login when needed
         Ok(true)
     }
+
+    // Logged-in client, or the text to show instead
+    async fn logged_in_client(&mut self, chat_id: ChatId) -> Result<&mut SynologyClient, String> {
+        match self.ensure_logged_in(Some(chat_id)).await {
+            Ok(true) => self.client.as_mut().ok_or_else(|| "Synology client not available".to_string()),
+            Ok(false) => Err("Could not login to Synology NAS. Please check your SYNOLOGY_USERNAME and SYNOLOGY_PASSWORD environment variables.".to_string()),
+            Err(e) => Err(format!("Failed to login to Synology NAS: {}", e)),
+        }
+    }
 }
 
-// Function to check if a chat ID is authorized
-fn is_authorized_chat(chat_id: i64) -> bool {
-    if let Ok(allowed_chat_id_str) = std::env::var("STB_ALLOWED_CHAT_ID") {
-        if let Ok(allowed_chat_id) = allowed_chat_id_str.parse::<i64>() {
-            return chat_id == allowed_chat_id;
+// One NAS profile at runtime: its DSM connection and SSH auto-disable timer
+struct Nas {
+    name: String,
+    // "[backup] " in front of replies, so they say which NAS they are about; empty with a single NAS
+    prefix: String,
+    synology: Arc<Mutex<SynologyConfig>>,
+    ssh_timer: Arc<SshAutoDisable>,
+    // Key of the NAS in the state file, `None` for the first profile
+    state_key: Option<String>,
+    wol: Option<WolTarget>,
+}
+
+// All NAS profiles. Commands without a NAS name act on the one picked in the chat's main menu,
+// or on the first profile.
+struct NasUnits {
+    units: Vec<Nas>,
+    // Index of the selected NAS per chat
+    selected: Mutex<HashMap<ChatId, usize>>,
+    // Folders behind the File Station buttons
+    file_tokens: Mutex<FileTokens>,
+}
+
+impl NasUnits {
+    fn new(units: Vec<Nas>) -> Self {
+        NasUnits { units, selected: Mutex::new(HashMap::new()), file_tokens: Mutex::new(FileTokens::default()) }
+    }
+
+    fn get(&self, name: &str) -> Option<&Nas> {
+        self.units.iter().find(|nas| nas.name.eq_ignore_ascii_case(name))
+    }
+
+    fn is_multi(&self) -> bool {
+        self.units.len() > 1
+    }
+
+    async fn selected(&self, chat_id: ChatId) -> &Nas {
+        let idx = self.selected.lock().await.get(&chat_id).copied().unwrap_or_default();
+        &self.units[idx]
+    }
+
+    async fn select(&self, chat_id: ChatId, name: &str) -> Option<&Nas> {
+        let idx = self.units.iter().position(|nas| nas.name.eq_ignore_ascii_case(name))?;
+        self.selected.lock().await.insert(chat_id, idx);
+        Some(&self.units[idx])
+    }
+
+    // Split a leading NAS name off command arguments: "backup on" is (backup, "on")
+    fn split_target<'a>(&self, arg: &'a str) -> (Option<&Nas>, &'a str) {
+        let arg = arg.trim();
+        let (first, rest) = arg.split_once(char::is_whitespace).unwrap_or((arg, ""));
+        match self.get(first) {
+            Some(nas) => (Some(nas), rest.trim_start()),
+            None => (None, arg),
+        }
+    }
+
+    // The named NAS, or the chat's selected one
+    async fn target<'a>(&self, chat_id: ChatId, arg: &'a str) -> (&Nas, &'a str) {
+        match self.split_target(arg) {
+            (Some(nas), rest) => (nas, rest),
+            (None, arg) => (self.selected(chat_id).await, arg),
+        }
+    }
+
+    fn unknown_text(&self, name: &str) -> String {
+        let known: Vec<&str> = self.units.iter().map(|nas| nas.name.as_str()).collect();
+        format!("Unknown NAS '{}'. Known NAS: {}", name, known.join(", "))
+    }
+
+    // Credentials from OpenBao may have changed while it was sealed
+    async fn reload_credentials(&self) {
+        for nas in &self.units {
+            nas.synology.lock().await.reload_credentials().await;
         }
     }
-    false
+
+    async fn main_menu(&self, chat_id: ChatId) -> InlineKeyboardMarkup {
+        let names: Vec<&str> = self.units.iter().map(|nas| nas.name.as_str()).collect();
+        create_main_menu(&names, &self.selected(chat_id).await.name)
+    }
+}
+
+// Button data for an action on one NAS: "ssh_on@backup"
+fn nas_callback(data: &str, nas: &str) -> String {
+    format!("{}@{}", data, nas)
 }
 
-// Function to create the main menu keyboard
-fn create_main_menu() -> InlineKeyboardMarkup {
+// Minimum role needed to press a menu button; unknown callbacks require admin
+fn callback_required_role(data: &str) -> Role {
+    // Without the NAS name of "ssh_on@backup"
+    let data = data.split_once('@').map_or(data, |(data, _)| data);
+    match data {
+        CALLBACK_SSH_MENU | CALLBACK_SERVICES | CALLBACK_STORAGE | CALLBACK_SYSTEM | CALLBACK_SYSTEM_ALL | CALLBACK_SETTINGS | CALLBACK_BACK => Role::Viewer,
+        d if d.starts_with(CALLBACK_NAS_SELECT) => Role::Viewer,
+        CALLBACK_SSH_ON | CALLBACK_SSH_OFF | CALLBACK_SSH_EXTEND | CALLBACK_UNSEAL => Role::Operator,
+        d if d.starts_with(CALLBACK_SSH_ON_FOR) || d.starts_with(CALLBACK_UNSEAL_JOIN) => Role::Operator,
+        d if d.starts_with(CALLBACK_SERVICE_ON) || d.starts_with(CALLBACK_SERVICE_OFF) => Role::Operator,
+        d if d.starts_with(CALLBACK_FILES) => Role::Operator,
+        d if d.starts_with(CALLBACK_SEAL_CONFIRM) || d.starts_with(CALLBACK_SEAL_CANCEL) => Role::Admin,
+        d if d.starts_with(CALLBACK_POWER_CONFIRM) || d.starts_with(CALLBACK_POWER_CANCEL) => Role::Admin,
+        _ => Role::Admin,
+    }
+}
+
+// Command of a message; "/ssh" texts the command parser rejects (e.g. "/ssh@OtherBot on") are
+// still handled as /ssh, like the plain-text fallback did before the dispatcher checked roles
+fn parse_command(msg: &Message, me: &Me) -> Option<Command> {
+    let text = msg.text()?;
+    Command::parse(text, me.username()).ok().or_else(|| {
+        let arg = text.split_once(char::is_whitespace).map_or("", |(_, arg)| arg.trim());
+        text.starts_with("/ssh").then(|| Command::Ssh(arg.to_string()))
+    })
+}
+
+// Minimum role needed for an update: commands and callbacks declare their own, plain messages need viewer
+fn required_role(update: &Update, me: &Me) -> Role {
+    match &update.kind {
+        UpdateKind::Message(msg) => parse_command(msg, me)
+            .map(|cmd| cmd.required_role())
+            .unwrap_or(Role::Viewer),
+        UpdateKind::CallbackQuery(q) => q.data.as_deref()
+            .map(callback_required_role)
+            .unwrap_or(Role::Viewer),
+        _ => Role::Viewer,
+    }
+}
+
+// Function to create the main menu keyboard; the buttons act on the selected NAS
+fn create_main_menu(nas_names: &[&str], selected: &str) -> InlineKeyboardMarkup {
     let mut keyboard: Vec<Vec<InlineKeyboardButton>> = Vec::new();
 
+    // NAS selector, only with several NAS profiles
+    if nas_names.len() > 1 {
+        let buttons: Vec<InlineKeyboardButton> = nas_names
+            .iter()
+            .map(|name| InlineKeyboardButton::callback(
+                if *name == selected { format!("✅ {}", name) } else { format!("🗄️ {}", name) },
+                format!("{}{}", CALLBACK_NAS_SELECT, name)
+            ))
+            .collect();
+        for row in buttons.chunks(3) {
+            keyboard.push(row.to_vec());
+        }
+    }
+
     // SSH Control button
-    let ssh_button = InlineKeyboardButton::callback("🖥️ SSH Control", CALLBACK_SSH_MENU);
+    let ssh_button = InlineKeyboardButton::callback("🖥️ SSH Control", nas_callback(CALLBACK_SSH_MENU, selected));
+
+    // Services button (SMB, NFS, FTP, ...)
+    let services_button = InlineKeyboardButton::callback("🧩 Services", nas_callback(CALLBACK_SERVICES, selected));
+
+    // Storage health button
+    let storage_button = InlineKeyboardButton::callback("💾 Storage", nas_callback(CALLBACK_STORAGE, selected));
+
+    // System info and load button
+    let system_button = InlineKeyboardButton::callback("📊 System", nas_callback(CALLBACK_SYSTEM, selected));
 
     // Add buttons to keyboard
-    keyboard.push(vec![ssh_button]);
+    keyboard.push(vec![ssh_button, services_button]);
+    keyboard.push(vec![storage_button, system_button]);
+
+    InlineKeyboardMarkup::new(keyboard)
+}
+
+// Keyboard below the storage dashboard
+fn create_storage_menu(nas: &str) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("🔄 Refresh", nas_callback(CALLBACK_STORAGE, nas)),
+        InlineKeyboardButton::callback("🔙 Back to Main Menu", CALLBACK_BACK),
+    ]])
+}
+
+// Keyboard below the system status; Refresh edits the message in place
+fn create_system_menu(refresh: String) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("🔄 Refresh", refresh),
+        InlineKeyboardButton::callback("🔙 Back to Main Menu", CALLBACK_BACK),
+    ]])
+}
+
+// Text bar for a percentage, e.g. "▓▓▓▓▓▓░░░░"
+fn usage_bar(percent: f64, width: usize) -> String {
+    let filled = ((percent.clamp(0.0, 100.0) / 100.0) * width as f64).round() as usize;
+    format!("{}{}", "▓".repeat(filled), "░".repeat(width - filled))
+}
+
+// Byte count with binary units, as DSM shows them ("3.6 TB")
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["B", "KB", "MB", "GB", "TB", "PB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+// Compact storage dashboard: usage bar per volume, pool health, disk temperature and SMART
+fn format_storage_info(info: &StorageInfo) -> String {
+    let mut lines = vec!["💾 Storage".to_string()];
+
+    lines.push(String::new());
+    lines.push("Volumes".to_string());
+    for volume in &info.volumes {
+        let icon = if volume.status.eq_ignore_ascii_case("normal") { "✅" } else { "⚠️" };
+        let name = if volume.vol_path.is_empty() { &volume.id } else { &volume.vol_path };
+        let fs = volume.fs_type.as_deref().map(|fs| format!(" ({})", fs)).unwrap_or_default();
+        let status = if volume.status.eq_ignore_ascii_case("normal") { String::new() } else { format!(" – {}", volume.status.to_uppercase()) };
+        lines.push(format!("{} {}{}{}", icon, name, fs, status));
+        lines.push(format!(
+            "{} {:.0}% · {} of {}",
+            usage_bar(volume.size.used_percent(), 10),
+            volume.size.used_percent(),
+            format_bytes(volume.size.used),
+            format_bytes(volume.size.total)
+        ));
+    }
+
+    lines.push(String::new());
+    lines.push("Storage pools".to_string());
+    for (idx, pool) in info.storage_pools.iter().enumerate() {
+        let (icon, status) = if pool.is_crashed() {
+            ("❌", pool.status.to_uppercase())
+        } else if pool.is_degraded() {
+            ("⚠️", pool.status.to_uppercase())
+        } else {
+            ("✅", pool.status.clone())
+        };
+        let number = pool.num_id.unwrap_or(idx as u32 + 1);
+        lines.push(format!("{} Pool {} · {} · {} disk(s) · {}", icon, number, pool.raid_label(), pool.disks.len(), status));
+    }
+
+    lines.push(String::new());
+    lines.push("Disks".to_string());
+    for disk in &info.disks {
+        let healthy = disk.status.eq_ignore_ascii_case("normal") && disk.smart_ok();
+        let temp = disk.temp.map(|t| format!(" · {} °C", t)).unwrap_or_default();
+        let smart = disk.smart_status.as_deref().unwrap_or("n/a");
+        let name = if disk.name.is_empty() { &disk.id } else { &disk.name };
+        lines.push(format!(
+            "{} {} · {} · {}{} · SMART {}",
+            if healthy { "✅" } else { "⚠️" },
+            name,
+            disk.model.trim(),
+            format_bytes(disk.size_total),
+            temp,
+            smart
+        ));
+    }
+
+    lines.join("\n")
+}
+
+// System status: model, DSM version, uptime, temperature and the current load
+fn format_system_status(info: &SystemInfo, util: &SystemUtilization) -> String {
+    let mut lines = vec![format!("📊 System · {}", info.model)];
+
+    lines.push(format!("{} · serial {}", info.firmware_ver, info.serial));
+    let uptime = info.uptime().map(format_duration).unwrap_or_else(|| "unknown".to_string());
+    let temp = match info.sys_temp {
+        Some(temp) if info.temperature_warning => format!(" · ⚠️ {} °C", temp),
+        Some(temp) => format!(" · {} °C", temp),
+        None => String::new(),
+    };
+    lines.push(format!("Uptime {}{}", uptime, temp));
+
+    lines.push(String::new());
+    let cpu = util.cpu.percent();
+    lines.push(format!(
+        "CPU {:>3}% {} · load {:.2} / {:.2} / {:.2}",
+        cpu,
+        usage_bar(cpu as f64, 10),
+        util.cpu.load_1min as f64 / 100.0,
+        util.cpu.load_5min as f64 / 100.0,
+        util.cpu.load_15min as f64 / 100.0
+    ));
+    let memory = &util.memory;
+    lines.push(format!(
+        "RAM {:>3}% {} · {} of {}",
+        memory.real_usage,
+        usage_bar(memory.real_usage as f64, 10),
+        format_bytes(memory.used_bytes()),
+        format_bytes(memory.total_bytes())
+    ));
+    if memory.total_swap > 0 {
+        lines.push(format!(
+            "Swap {:>2}% {} · {} of {}",
+            memory.swap_usage,
+            usage_bar(memory.swap_usage as f64, 10),
+            format_bytes(memory.swap_used_bytes()),
+            format_bytes(memory.total_swap * 1024)
+        ));
+    }
+
+    let (rx, tx) = util.network_total();
+    lines.push(format!("Network ↓ {}/s · ↑ {}/s", format_bytes(rx), format_bytes(tx)));
+    if let Some(disk) = &util.disk.total {
+        lines.push(format!(
+            "Disks read {}/s · write {}/s · {}% busy",
+            format_bytes(disk.read_byte),
+            format_bytes(disk.write_byte),
+            disk.utilization
+        ));
+    }
+
+    if !info.time.is_empty() {
+        lines.push(String::new());
+        lines.push(format!("Updated {}", info.time));
+    }
+    lines.join("\n")
+}
+
+// One line per NAS for the system overview
+fn format_system_summary(info: &SystemInfo, util: &SystemUtilization) -> String {
+    let uptime = info.uptime().map(format_duration).unwrap_or_else(|| "unknown".to_string());
+    let temp = info.sys_temp.map(|t| format!(" · {} °C", t)).unwrap_or_default();
+    format!(
+        "{} {} · up {} · CPU {}% · RAM {}%{}",
+        if info.temperature_warning { "⚠️" } else { "✅" },
+        info.model,
+        uptime,
+        util.cpu.percent(),
+        util.memory.real_usage,
+        temp
+    )
+}
+
+// Seal status table: one line per cluster node with seal state, progress and HA role
+fn format_seal_status(nodes: &[NodeStatus]) -> String {
+    let unsealed = nodes.iter().filter(|n| n.seal.as_ref().is_ok_and(|s| s.initialized && !s.sealed)).count();
+    let cluster = nodes.iter()
+        .find_map(|n| n.health.as_ref().and_then(|h| h.cluster_name.clone()))
+        .map(|name| format!(" cluster {}", name))
+        .unwrap_or_default();
+    let mut text = format!("🔐 OpenBao{}: {} of {} node(s) unsealed\n", cluster, unsealed, nodes.len());
+    for node in nodes {
+        let line = match &node.seal {
+            Ok(status) if !status.initialized => format!("❔ {}: NOT INITIALIZED", node.addr),
+            Ok(status) if status.sealed => format!("🔒 {}: SEALED (progress {}/{})", node.addr, status.progress, status.t),
+            Ok(_) => {
+                let mut line = format!("🔓 {}: unsealed", node.addr);
+                if let Some(role) = node.role() {
+                    line.push_str(&format!(", {}", role));
+                }
+                if let Some(leader) = &node.leader && leader.ha_enabled && !leader.is_self && !leader.leader_address.is_empty() {
+                    line.push_str(&format!(" of {}", leader.leader_address));
+                }
+                if let Some(health) = &node.health && !health.version.is_empty() {
+                    line.push_str(&format!(" (v{})", health.version));
+                }
+                line
+            },
+            Err(e) => format!("⚠️ {}: unreachable ({})", node.addr, e),
+        };
+        text.push('\n');
+        text.push_str(&line);
+    }
+    text
+}
+
+// OpenBao health: seal table plus replication modes, Raft autopilot and the bot's token
+fn format_bao_health(
+    nodes: &[NodeStatus],
+    autopilot: Option<&Result<AutopilotState, BaoError>>,
+    token: Option<&Result<TokenInfo, BaoError>>
+) -> String {
+    let mut text = format_seal_status(nodes);
+
+    // Replication only shows up if a node reports it as enabled
+    let replication: Vec<String> = nodes.iter()
+        .filter_map(|n| n.health.as_ref().map(|h| (&n.addr, h)))
+        .flat_map(|(addr, h)| {
+            [("performance", &h.replication_performance_mode), ("DR", &h.replication_dr_mode)]
+                .into_iter()
+                .filter_map(move |(kind, mode)| mode.as_deref()
+                    .filter(|m| *m != "disabled")
+                    .map(|m| format!("{}: {} replication {}", addr, kind, m)))
+        })
+        .collect();
+    if !replication.is_empty() {
+        text.push_str("\n\n🔁 Replication\n");
+        text.push_str(&replication.join("\n"));
+    }
+
+    match autopilot {
+        Some(Ok(state)) => {
+            text.push_str(&format!(
+                "\n\n{} Raft: {}, leader {}, failure tolerance {}",
+                if state.healthy { "✅" } else { "⚠️" },
+                if state.healthy { "healthy" } else { "UNHEALTHY" },
+                state.leader,
+                state.failure_tolerance
+            ));
+            for (id, server) in &state.servers {
+                let mut line = format!(
+                    "\n{} {} ({}): {}, index {}",
+                    if server.healthy { "🟢" } else { "🔴" },
+                    id,
+                    server.address,
+                    server.status,
+                    server.last_index
+                );
+                if let Some(contact) = &server.last_contact && !server.healthy {
+                    line.push_str(&format!(", last contact {}", contact));
+                }
+                text.push_str(&line);
+            }
+        },
+        Some(Err(e)) => text.push_str(&format!("\n\n⚠️ Raft autopilot state unavailable: {}", e)),
+        None => {}
+    }
+
+    match token {
+        Some(Ok(info)) => {
+            let ttl = if info.ttl == 0 { "no expiry".to_string() } else { format!("TTL {}", format_duration(Duration::from_secs(info.ttl))) };
+            text.push_str(&format!("\n\n🎫 Token {} ({}), policies: {}", info.display_name, ttl, info.policies.join(", ")));
+        },
+        Some(Err(e)) => text.push_str(&format!("\n\n⚠️ Token lookup failed: {}", e)),
+        None => text.push_str("\n\nNo OpenBao token configured, Raft and token details are not available."),
+    }
+    text
+}
+
+// Confirmation buttons for /seal
+fn create_seal_confirm_menu(nonce: &str) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("🔒 Seal now", format!("{}{}", CALLBACK_SEAL_CONFIRM, nonce)),
+        InlineKeyboardButton::callback("Cancel", format!("{}{}", CALLBACK_SEAL_CANCEL, nonce)),
+    ]])
+}
+
+// Function to create the services menu: one toggle button per service, showing its live state
+fn create_services_menu(statuses: &[(&'static ServiceDef, Result<bool, SynologyClientError>)], nas: &str) -> InlineKeyboardMarkup {
+    let mut keyboard: Vec<Vec<InlineKeyboardButton>> = Vec::new();
+
+    let buttons: Vec<InlineKeyboardButton> = statuses
+        .iter()
+        .map(|(service, status)| match status {
+            Ok(true) => InlineKeyboardButton::callback(
+                format!("🟢 {} – disable", service.label),
+                nas_callback(&format!("{}{}", CALLBACK_SERVICE_OFF, service.name), nas)
+            ),
+            Ok(false) => InlineKeyboardButton::callback(
+                format!("⚪ {} – enable", service.label),
+                nas_callback(&format!("{}{}", CALLBACK_SERVICE_ON, service.name), nas)
+            ),
+            Err(_) => InlineKeyboardButton::callback(
+                format!("⚠️ {}", service.label),
+                nas_callback(CALLBACK_SERVICES, nas)
+            ),
+        })
+        .collect();
+    for row in buttons.chunks(2) {
+        keyboard.push(row.to_vec());
+    }
+
+    keyboard.push(vec![
+        InlineKeyboardButton::callback("🔄 Refresh", nas_callback(CALLBACK_SERVICES, nas)),
+        InlineKeyboardButton::callback("🔙 Back to Main Menu", CALLBACK_BACK),
+    ]);
 
     InlineKeyboardMarkup::new(keyboard)
 }
 
+// One line per service with its current state
+fn format_service_statuses(statuses: &[(&'static ServiceDef, Result<bool, SynologyClientError>)]) -> String {
+    statuses
+        .iter()
+        .map(|(service, status)| match status {
+            Ok(true) => format!("🟢 {}: enabled", service.label),
+            Ok(false) => format!("⚪ {}: disabled", service.label),
+            Err(e) => format!("⚠️ {}: unknown ({})", service.label, e),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Service states of one NAS, or why they could not be read
+type ServiceColumn<'a> = (&'a str, Result<Vec<(&'static ServiceDef, Result<bool, SynologyClientError>)>, String>);
+
+// Services of several NAS side by side: one row per service with a state icon per NAS
+fn format_service_table(columns: &[ServiceColumn]) -> String {
+    let names: Vec<&str> = columns.iter().map(|(name, _)| *name).collect();
+    let mut lines = vec![format!("DSM services ({}):", names.join(" | "))];
+    for service in SERVICES {
+        let cells: Vec<&str> = columns
+            .iter()
+            .map(|(_, statuses)| match statuses {
+                Ok(statuses) => match statuses.iter().find(|(s, _)| s.name == service.name) {
+                    Some((_, Ok(true))) => "🟢",
+                    Some((_, Ok(false))) => "⚪",
+                    _ => "⚠️",
+                },
+                Err(_) => "❔",
+            })
+            .collect();
+        lines.push(format!("{}: {}", service.label, cells.join(" | ")));
+    }
+    for (name, statuses) in columns {
+        if let Err(e) = statuses {
+            lines.push(format!("❔ {}: {}", name, e));
+        }
+    }
+    lines.join("\n")
+}
+
+// One line per NAS for the storage overview: overall health, usage, disks and hottest disk
+fn format_storage_summary(info: &StorageInfo) -> String {
+    let healthy = info.volumes.iter().all(|v| v.status.eq_ignore_ascii_case("normal"))
+        && info.storage_pools.iter().all(|p| !p.is_crashed() && !p.is_degraded())
+        && info.disks.iter().all(|d| d.status.eq_ignore_ascii_case("normal") && d.smart_ok());
+    let used: u64 = info.volumes.iter().map(|v| v.size.used).sum();
+    let total: u64 = info.volumes.iter().map(|v| v.size.total).sum();
+    let percent = if total == 0 { 0.0 } else { used as f64 * 100.0 / total as f64 };
+    let temp = info.disks.iter().filter_map(|d| d.temp).max().map(|t| format!(" · max {} °C", t)).unwrap_or_default();
+    format!(
+        "{} {} volume(s) · {:.0}% of {} used · {} disk(s){}",
+        if healthy { "✅" } else { "⚠️" },
+        info.volumes.len(),
+        percent,
+        format_bytes(total),
+        info.disks.len(),
+        temp
+    )
+}
+
 // Function to create the SSH menu keyboard based on current status
-fn create_ssh_menu(ssh_enabled: bool) -> InlineKeyboardMarkup {
+fn create_ssh_menu(ssh_enabled: bool, nas: &str) -> InlineKeyboardMarkup {
     let mut keyboard: Vec<Vec<InlineKeyboardButton>> = Vec::new();
 
     // Add the appropriate button based on current SSH status
     if ssh_enabled {
         // SSH is enabled, show disable option
-        let ssh_off_button = InlineKeyboardButton::callback("❌ Disable SSH", CALLBACK_SSH_OFF);
+        let ssh_off_button = InlineKeyboardButton::callback("❌ Disable SSH", nas_callback(CALLBACK_SSH_OFF, nas));
         keyboard.push(vec![ssh_off_button]);
     } else {
         // SSH is disabled, show enable option
-        let ssh_on_button = InlineKeyboardButton::callback("✅ Enable SSH", CALLBACK_SSH_ON);
+        let ssh_on_button = InlineKeyboardButton::callback("✅ Enable SSH", nas_callback(CALLBACK_SSH_ON, nas));
         keyboard.push(vec![ssh_on_button]);
+
+        // Timed enable options, SSH is switched off again automatically
+        let timed_buttons = [15, 30, 60]
+            .iter()
+            .map(|minutes| InlineKeyboardButton::callback(
+                format!("⏱️ {} min", minutes),
+                nas_callback(&format!("{}{}", CALLBACK_SSH_ON_FOR, minutes), nas)
+            ))
+            .collect();
+        keyboard.push(timed_buttons);
     }
 
     // Back button
@@ -167,47 +802,177 @@ fn create_ssh_menu(ssh_enabled: bool) -> InlineKeyboardMarkup {
 enum Command {
     #[command(description = "Start the bot.")]
     Start,
-    #[command(description = "Get SSH status or enable/disable SSH. Usage: /ssh [on|off]")]
+    #[command(description = "Get SSH status or enable/disable SSH. Usage: /ssh [nas] [on|off]")]
     Ssh(String),
-    #[command(description = "Enable SSH service (same as /ssh on)")]
-    SshOn,
-    #[command(description = "Disable SSH service (same as /ssh off)")]
-    SshOff,
+    #[command(description = "Enable SSH service (same as /ssh on). Usage: /ssh_on [nas]")]
+    SshOn(String),
+    #[command(description = "Disable SSH service (same as /ssh off). Usage: /ssh_off [nas]")]
+    SshOff(String),
+    #[command(description = "Show or switch DSM services. Usage: /service [nas] [name] [on|off]")]
+    Service(String),
+    #[command(description = "Show volumes, storage pools and disks. Usage: /storage [nas]")]
+    Storage(String),
+    #[command(description = "Show system info and the current load. Usage: /status [nas]")]
+    Status(String),
+    #[command(description = "Browse the shared folders. Usage: /files [nas] [path]")]
+    Files(String),
+    #[command(description = "Reboot the NAS (asks for confirmation). Usage: /reboot [nas]")]
+    Reboot(String),
+    #[command(description = "Shut down the NAS (asks for confirmation). Usage: /shutdown [nas]")]
+    Shutdown(String),
+    #[command(description = "Power the NAS on with Wake-on-LAN. Usage: /wake [nas]")]
+    Wake(String),
     #[command(description = "Show OpenBao seal status")]
     SealStatus,
-    #[command(description = "Unseal OpenBao (asks for TOTP code)")]
+    #[command(description = "Start a collective OpenBao unseal session for the key holders")]
     Unseal,
+    #[command(description = "Seal OpenBao on all nodes (emergency, asks for confirmation)")]
+    Seal,
+    #[command(description = "Make the active OpenBao node step down")]
+    BaoStepdown,
+    #[command(description = "Show OpenBao health with HA and replication details")]
+    BaoHealth,
 }
 
-// Handle commands from BotCommands enum
-async fn answer_command(
+impl Command {
+    // Minimum role needed to run the command
+    fn required_role(&self) -> Role {
+        match self {
+            Command::Start | Command::Storage(_) | Command::Status(_) | Command::SealStatus | Command::BaoHealth => Role::Viewer,
+            // Status queries, possibly with a NAS name: "/ssh backup", "/service backup smb"
+            Command::Ssh(arg) | Command::Service(arg) if !is_switch(arg) => Role::Viewer,
+            Command::Ssh(_) | Command::SshOn(_) | Command::SshOff(_) | Command::Service(_) | Command::Files(_) | Command::Wake(_) | Command::Unseal => Role::Operator,
+            Command::Reboot(_) | Command::Shutdown(_) | Command::Seal | Command::BaoStepdown => Role::Admin,
+        }
+    }
+}
+
+// Whether command arguments switch something on or off
+fn is_switch(arg: &str) -> bool {
+    arg.split_whitespace()
+        .any(|word| matches!(word.to_lowercase().as_str(), "on" | "off" | "enable" | "disable"))
+}
+
+// Messages of a key holder without a role of their own (allowed only through a group chat) pass
+// the gate while their share link is open, but only the share itself is taken
+async fn share_holder_handler(
     bot: Bot,
+    update: Update,
     msg: Message,
-    cmd: Command,
-    synology_config: Arc<Mutex<SynologyConfig>>,
+    auth: Authorization,
+    nas_units: Arc<NasUnits>,
     bao_config: Arc<Option<BaoConfig>>,
-    pending_unseal: Arc<Mutex<Option<UnsealSession>>>
+    unseal_sessions: Arc<Mutex<UnsealSessions>>
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if submit_share_message(&bot, &msg, &nas_units, &bao_config, &unseal_sessions).await? {
+        return Ok(());
+    }
+    unauthorized_handler(bot, update, auth).await
+}
+
+// Reply to updates that did not pass the authorization gate in the dispatcher
+async fn unauthorized_handler(
+    bot: Bot,
+    update: Update,
+    auth: Authorization
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
-    // Check if the chat is authorized
-    if !is_authorized_chat(msg.chat.id.0) {
-        let first_name = msg.from()
-            .map(|user| user.first_name.clone())
-            .unwrap_or_else(|| String::from("Unknown"));
+    let first_name = update.user()
+        .map(|user| user.first_name.clone())
+        .unwrap_or_else(|| String::from("Unknown"));
+    let chat_id = update.chat().map(|chat| chat.id.0).unwrap_or_default();
+
+    let text = match auth.granted {
+        None => {
+            warn!("Unauthorized access attempt from user {} with chat ID {}", first_name, chat_id);
+            format!("You ({}) are not authorized to use this bot. Your chat ID {} is not allowed.", first_name, chat_id)
+        },
+        Some(role) => {
+            warn!("User {} (role {}) in chat {} tried an action that requires the {} role", first_name, role, chat_id, auth.required);
+            format!("This action requires the {} role, you are {}.", auth.required, role)
+        }
+    };
+
+    match update.kind {
+        UpdateKind::Message(msg) => {
+            bot.send_message(msg.chat.id, text).await?;
+        },
+        UpdateKind::CallbackQuery(q) => {
+            bot.answer_callback_query(q.id)
+                .text(text)
+                .show_alert(true)
+                .await?;
+        },
+        UpdateKind::InlineQuery(q) => {
+            bot.answer_inline_query(q.id, vec![])
+                .cache_time(0)
+                .await?;
+        },
+        _ => {}
+    }
+
+    Ok(())
+}
 
-        warn!("Unauthorized access attempt from user {} with chat ID {}", first_name, msg.chat.id.0);
+// System info and load of one NAS, or the text to show instead
+async fn load_system_status(nas: &Nas, chat_id: ChatId) -> Result<(SystemInfo, SystemUtilization), String> {
+    let mut config = nas.synology.lock().await;
+    let client = config.logged_in_client(chat_id).await?;
+    let info = client.get_system_info().await.map_err(|e| format!("Failed to load system info: {}", e))?;
+    let util = client.get_utilization().await.map_err(|e| format!("Failed to load system utilization: {}", e))?;
+    Ok((info, util))
+}
 
-        bot.send_message(
-            msg.chat.id,
-            format!("Hello {}({}), unfortunately are not authorized to use this bot.", first_name, msg.chat.id.0)
-        ).await?;
+// Summary line per NAS for /status without a NAS name
+async fn system_overview_text(nas_units: &NasUnits, chat_id: ChatId) -> String {
+    let mut lines = vec!["📊 System".to_string(), String::new()];
+    for nas in &nas_units.units {
+        let summary = match load_system_status(nas, chat_id).await {
+            Ok((info, util)) => format_system_summary(&info, &util),
+            Err(e) => format!("❔ {}", e),
+        };
+        lines.push(format!("{}{}", nas.prefix, summary));
+    }
+    lines.push(String::new());
+    lines.push("Details: /status <nas>".to_string());
+    lines.join("\n")
+}
 
-        return Ok(());
+// "SSH service is currently enabled" for one NAS, with a pending automatic disable
+async fn ssh_status_text(nas: &Nas, chat_id: ChatId) -> String {
+    let mut config = nas.synology.lock().await;
+    let status = match config.logged_in_client(chat_id).await {
+        Ok(client) => client.get_ssh_status().await.map_err(|e| format!("Failed to get SSH status: {}", e)),
+        Err(e) => Err(e),
+    };
+    match status {
+        Ok(status) => {
+            let status_text = if status { "enabled" } else { "disabled" };
+            let timer_text = match nas.ssh_timer.remaining().await {
+                Some(left) if status && left.is_zero() => " (automatic disable failed, retrying)".to_string(),
+                Some(left) if status => format!(" (automatic disable in {})", format_duration(left)),
+                _ => String::new(),
+            };
+            format!("{}SSH service is currently {}{}", nas.prefix, status_text, timer_text)
+        },
+        Err(e) => format!("{}{}", nas.prefix, e),
     }
+}
+
+// Handle commands from BotCommands enum
+async fn answer_command(
+    bot: Bot,
+    msg: Message,
+    cmd: Command,
+    nas_units: Arc<NasUnits>,
+    bao_config: Arc<Option<BaoConfig>>,
+    unseal_sessions: Arc<Mutex<UnsealSessions>>,
+    power_confirmations: Arc<Mutex<PowerConfirmations>>
+) -> Result<(), Box<dyn Error + Send + Sync>> {
     info!("Command {:?} received from chat {}", cmd, msg.chat.id.0);
     match cmd {
         Command::Start => {
             // Create the main menu keyboard
-            let keyboard = create_main_menu();
+            let keyboard = nas_units.main_menu(msg.chat.id).await;
 
             let chat_json = serde_json::to_string_pretty(&msg.chat).unwrap();
             info!("Chat info: {}", chat_json);
@@ -221,199 +986,503 @@ async fn answer_command(
             .await?;
         }
         Command::Ssh(arg) => {
+            let (target, arg) = nas_units.split_target(&arg);
+            if arg.is_empty() {
+                // Just /ssh - get status; of every NAS unless one is named
+                let mut lines = Vec::new();
+                match target {
+                    Some(nas) => lines.push(ssh_status_text(nas, msg.chat.id).await),
+                    None => for nas in &nas_units.units {
+                        lines.push(ssh_status_text(nas, msg.chat.id).await);
+                    },
+                }
+                bot.send_message(msg.chat.id, lines.join("\n")).await?;
+                return Ok(());
+            }
+            let nas = match target {
+                Some(nas) => nas,
+                None => nas_units.selected(msg.chat.id).await,
+            };
+            let ssh_timer = &nas.ssh_timer;
+
             // Get the synology config
-            let mut config = synology_config.lock().await;
+            let mut config = nas.synology.lock().await;
 
             // Ensure logged in
-            match config.ensure_logged_in().await {
-                Ok(true) => {
-                    // Now we're logged in, proceed with SSH operations
-                    if let Some(client) = &mut config.client {
-                        if arg.is_empty() {
-                            // Just /ssh - get status
-                            match client.get_ssh_status().await {
-                                Ok(status) => {
-                                    let status_text = if status { "enabled" } else { "disabled" };
-                                    bot.send_message(
-                                        msg.chat.id,
-                                        format!("SSH service is currently {}", status_text)
-                                    ).await?;
-                                },
-                                Err(e) => {
-                                    bot.send_message(
-                                        msg.chat.id,
-                                        format!("Failed to get SSH status: {}", e)
-                                    ).await?;
-                                }
-                            }
-                        } else {
-                            // /ssh on or /ssh off - set status
-                            let command = arg.to_lowercase();
-
-                            if command == "on" || command == "enable" {
-                                match client.toggle_ssh(true).await {
-                                    Ok(_) => {
-                                        info!("SSH service enabled by chat {}", msg.chat.id.0);
-                                        bot.send_message(
-                                            msg.chat.id,
-                                            "SSH service has been enabled"
-                                        ).await?;
-                                    },
-                                    Err(e) => {
-                                        error!("Failed to enable SSH service: {}", e);
-                                        bot.send_message(
-                                            msg.chat.id,
-                                            format!("Failed to enable SSH service: {}", e)
-                                        ).await?;
-                                    }
-                                }
-                            } else if command == "off" || command == "disable" {
-                                match client.toggle_ssh(false).await {
-                                    Ok(_) => {
-                                        info!("SSH service disabled by chat {}", msg.chat.id.0);
-                                        bot.send_message(
-                                            msg.chat.id,
-                                            "SSH service has been disabled"
-                                        ).await?;
-                                    },
-                                    Err(e) => {
-                                        error!("Failed to disable SSH service: {}", e);
-                                        bot.send_message(
-                                            msg.chat.id,
-                                            format!("Failed to disable SSH service: {}", e)
-                                        ).await?;
-                                    }
-                                }
-                            } else {
-                                bot.send_message(
-                                    msg.chat.id,
-                                    "Usage: /ssh [on|off] - Get SSH status or enable/disable SSH"
-                                ).await?;
+            let client = match config.logged_in_client(msg.chat.id).await {
+                Ok(client) => client,
+                Err(e) => {
+                    bot.send_message(msg.chat.id, format!("{}{}", nas.prefix, e)).await?;
+                    return Ok(());
+                }
+            };
+            // /ssh on [duration] or /ssh off - set status
+            let parts: Vec<String> = arg.split_whitespace().map(|p| p.to_lowercase()).collect();
+            let command = parts[0].as_str();
+            // Optional duration for a timed enable, e.g. /ssh on 30m
+            let duration = match parts.get(1) {
+                None => Some(None),
+                Some(d) => parse_duration(d).map(Some),
+            };
+
+            if (command == "on" || command == "enable") && parts.len() <= 2 && let Some(duration) = duration {
+                match client.toggle_ssh(true).await {
+                    Ok(_) => {
+                        info!("SSH service of {} enabled by chat {}", nas.name, msg.chat.id.0);
+                        let text = match duration {
+                            Some(duration) => {
+                                ssh_timer.schedule(msg.chat.id, duration).await;
+                                format!("{}SSH service has been enabled for {}", nas.prefix, format_duration(duration))
+                            },
+                            None => {
+                                ssh_timer.cancel().await;
+                                format!("{}SSH service has been enabled", nas.prefix)
                             }
-                        }
+                        };
+                        bot.send_message(msg.chat.id, text).await?;
+                    },
+                    Err(e) => {
+                        error!("Failed to enable SSH service of {}: {}", nas.name, e);
+                        bot.send_message(
+                            msg.chat.id,
+                            format!("{}Failed to enable SSH service: {}", nas.prefix, e)
+                        ).await?;
                     }
-                },
-                Ok(false) => {
-                    bot.send_message(
-                        msg.chat.id, 
-                        "Could not login to Synology NAS. Please check your SYNOLOGY_USERNAME and SYNOLOGY_PASSWORD environment variables."
-                    ).await?;
+                }
+            } else if (command == "off" || command == "disable") && parts.len() == 1 {
+                match client.toggle_ssh(false).await {
+                    Ok(_) => {
+                        info!("SSH service of {} disabled by chat {}", nas.name, msg.chat.id.0);
+                        ssh_timer.cancel().await;
+                        bot.send_message(
+                            msg.chat.id,
+                            format!("{}SSH service has been disabled", nas.prefix)
+                        ).await?;
+                    },
+                    Err(e) => {
+                        error!("Failed to disable SSH service of {}: {}", nas.name, e);
+                        bot.send_message(
+                            msg.chat.id,
+                            format!("{}Failed to disable SSH service: {}", nas.prefix, e)
+                        ).await?;
+                    }
+                }
+            } else {
+                bot.send_message(
+                    msg.chat.id,
+                    "Usage: /ssh [nas] [on [duration]|off] - Get SSH status or enable/disable SSH, e.g. /ssh on 30m"
+                ).await?;
+            }
+        }
+        Command::SshOn(ref arg) | Command::SshOff(ref arg) => {
+            let enable = matches!(cmd, Command::SshOn(_));
+            let (nas, rest) = nas_units.target(msg.chat.id, arg).await;
+            if !rest.is_empty() {
+                bot.send_message(msg.chat.id, nas_units.unknown_text(rest)).await?;
+                return Ok(());
+            }
+            let action_text = if enable { "enable" } else { "disable" };
+            let mut config = nas.synology.lock().await;
+            let client = match config.logged_in_client(msg.chat.id).await {
+                Ok(client) => client,
+                Err(e) => {
+                    bot.send_message(msg.chat.id, format!("{}{}", nas.prefix, e)).await?;
+                    return Ok(());
+                }
+            };
+            match client.toggle_ssh(enable).await {
+                Ok(_) => {
+                    info!("SSH service of {} {}d by chat {}", nas.name, action_text, msg.chat.id.0);
+                    nas.ssh_timer.cancel().await;
+                    bot.send_message(msg.chat.id, format!("{}SSH service has been {}d", nas.prefix, action_text)).await?;
                 },
                 Err(e) => {
-                    bot.send_message(
-                        msg.chat.id, 
-                        format!("Failed to login to Synology NAS: {}", e)
-                    ).await?;
+                    error!("Failed to {} SSH service of {}: {}", action_text, nas.name, e);
+                    bot.send_message(msg.chat.id, format!("{}Failed to {} SSH service: {}", nas.prefix, action_text, e)).await?;
                 }
             }
         }
-        Command::SshOn => {
-            let mut config = synology_config.lock().await;
-            match config.ensure_logged_in().await {
-                Ok(true) => {
-                    if let Some(client) = &mut config.client {
-                        match client.toggle_ssh(true).await {
-                            Ok(_) => {
-                                info!("SSH service enabled by chat {}", msg.chat.id.0);
-                                bot.send_message(msg.chat.id, "SSH service has been enabled").await?;
-                            },
-                            Err(e) => {
-                                error!("Failed to enable SSH service: {}", e);
-                                bot.send_message(msg.chat.id, format!("Failed to enable SSH service: {}", e)).await?;
+        Command::Service(arg) => {
+            let (target, arg) = nas_units.split_target(&arg);
+            let parts: Vec<String> = arg.split_whitespace().map(|p| p.to_lowercase()).collect();
+            let service = match parts.first() {
+                Some(name) => match find_service(name) {
+                    Some(service) => Some(service),
+                    None => {
+                        let known: Vec<&str> = SERVICES.iter().map(|s| s.name).collect();
+                        bot.send_message(msg.chat.id, format!("Unknown service '{}'. Known services: {}", name, known.join(", "))).await?;
+                        return Ok(());
+                    }
+                },
+                None => None,
+            };
+
+            // Just /service with several NAS: all of them side by side
+            if service.is_none() && target.is_none() && nas_units.is_multi() {
+                let mut columns = Vec::new();
+                for nas in &nas_units.units {
+                    let mut config = nas.synology.lock().await;
+                    let statuses = match config.logged_in_client(msg.chat.id).await {
+                        Ok(client) => client.get_all_service_status().await.map_err(|e| format!("Failed to get service status: {}", e)),
+                        Err(e) => Err(e),
+                    };
+                    columns.push((nas.name.as_str(), statuses));
+                }
+                bot.send_message(msg.chat.id, format_service_table(&columns)).await?;
+                return Ok(());
+            }
+            let nas = match target {
+                Some(nas) => nas,
+                None => nas_units.selected(msg.chat.id).await,
+            };
+
+            let mut config = nas.synology.lock().await;
+            let client = match config.logged_in_client(msg.chat.id).await {
+                Ok(client) => client,
+                Err(e) => {
+                    bot.send_message(msg.chat.id, format!("{}{}", nas.prefix, e)).await?;
+                    return Ok(());
+                }
+            };
+            match (service, parts.get(1).map(String::as_str)) {
+                (None, _) => {
+                    // Just /service - list all services
+                    match client.get_all_service_status().await {
+                        Ok(statuses) => {
+                            bot.send_message(msg.chat.id, format!("{}DSM services:\n{}", nas.prefix, format_service_statuses(&statuses))).await?;
+                        },
+                        Err(e) => {
+                            bot.send_message(msg.chat.id, format!("{}Failed to get service status: {}", nas.prefix, e)).await?;
+                        }
+                    }
+                },
+                (Some(service), None) => {
+                    match client.get_service_status(service).await {
+                        Ok(status) => {
+                            let status_text = if status { "enabled" } else { "disabled" };
+                            bot.send_message(msg.chat.id, format!("{}{} service is currently {}", nas.prefix, service.label, status_text)).await?;
+                        },
+                        Err(e) => {
+                            bot.send_message(msg.chat.id, format!("{}Failed to get {} status: {}", nas.prefix, service.label, e)).await?;
+                        }
+                    }
+                },
+                (Some(service), Some(action @ ("on" | "enable" | "off" | "disable"))) if parts.len() == 2 => {
+                    let enable = action == "on" || action == "enable";
+                    let action_text = if enable { "enable" } else { "disable" };
+                    match client.set_service(service, enable).await {
+                        Ok(_) => {
+                            info!("{} service of {} {}d by chat {}", service.label, nas.name, action_text, msg.chat.id.0);
+                            if service.name == SSH_SERVICE.name {
+                                nas.ssh_timer.cancel().await;
                             }
+                            bot.send_message(msg.chat.id, format!("{}{} service has been {}d", nas.prefix, service.label, action_text)).await?;
+                        },
+                        Err(e) => {
+                            error!("Failed to {} {} service of {}: {}", action_text, service.label, nas.name, e);
+                            bot.send_message(msg.chat.id, format!("{}Failed to {} {} service: {}", nas.prefix, action_text, service.label, e)).await?;
                         }
                     }
                 },
-                Ok(false) => {
-                    bot.send_message(msg.chat.id, "Could not login to Synology NAS. Please check your SYNOLOGY_USERNAME and SYNOLOGY_PASSWORD environment variables.").await?;
+                _ => {
+                    bot.send_message(msg.chat.id, "Usage: /service [nas] [name] [on|off] - Show or switch DSM services").await?;
+                }
+            }
+        }
+        Command::Storage(arg) => {
+            let (target, rest) = nas_units.split_target(&arg);
+            if !rest.is_empty() {
+                bot.send_message(msg.chat.id, nas_units.unknown_text(rest)).await?;
+                return Ok(());
+            }
+
+            // Just /storage with several NAS: one summary line each
+            if target.is_none() && nas_units.is_multi() {
+                let mut lines = vec!["💾 Storage".to_string(), String::new()];
+                for nas in &nas_units.units {
+                    let mut config = nas.synology.lock().await;
+                    let summary = match config.logged_in_client(msg.chat.id).await {
+                        Ok(client) => match client.get_storage_info().await {
+                            Ok(info) => format_storage_summary(&info),
+                            Err(e) => format!("❔ Failed to load storage info: {}", e),
+                        },
+                        Err(e) => format!("❔ {}", e),
+                    };
+                    lines.push(format!("{}{}", nas.prefix, summary));
+                }
+                lines.push(String::new());
+                lines.push("Details: /storage <nas>".to_string());
+                bot.send_message(msg.chat.id, lines.join("\n")).await?;
+                return Ok(());
+            }
+            let nas = match target {
+                Some(nas) => nas,
+                None => nas_units.selected(msg.chat.id).await,
+            };
+
+            let mut config = nas.synology.lock().await;
+            let client = match config.logged_in_client(msg.chat.id).await {
+                Ok(client) => client,
+                Err(e) => {
+                    bot.send_message(msg.chat.id, format!("{}{}", nas.prefix, e)).await?;
+                    return Ok(());
+                }
+            };
+            match client.get_storage_info().await {
+                Ok(info) => {
+                    bot.send_message(msg.chat.id, format!("{}{}", nas.prefix, format_storage_info(&info)))
+                        .reply_markup(create_storage_menu(&nas.name))
+                        .await?;
                 },
                 Err(e) => {
-                    bot.send_message(msg.chat.id, format!("Failed to login to Synology NAS: {}", e)).await?;
+                    error!("Failed to load storage info of {}: {}", nas.name, e);
+                    bot.send_message(msg.chat.id, format!("{}Failed to load storage info: {}", nas.prefix, e)).await?;
                 }
             }
         }
-        Command::SshOff => {
-            let mut config = synology_config.lock().await;
-            match config.ensure_logged_in().await {
-                Ok(true) => {
-                    if let Some(client) = &mut config.client {
-                        match client.toggle_ssh(false).await {
-                            Ok(_) => {
-                                info!("SSH service disabled by chat {}", msg.chat.id.0);
-                                bot.send_message(msg.chat.id, "SSH service has been disabled").await?;
-                            },
-                            Err(e) => {
-                                error!("Failed to disable SSH service: {}", e);
-                                bot.send_message(msg.chat.id, format!("Failed to disable SSH service: {}", e)).await?;
-                            }
-                        }
-                    }
+        Command::Status(arg) => {
+            let (target, rest) = nas_units.split_target(&arg);
+            if !rest.is_empty() {
+                bot.send_message(msg.chat.id, nas_units.unknown_text(rest)).await?;
+                return Ok(());
+            }
+
+            // Just /status with several NAS: one summary line each
+            if target.is_none() && nas_units.is_multi() {
+                bot.send_message(msg.chat.id, system_overview_text(&nas_units, msg.chat.id).await)
+                    .reply_markup(create_system_menu(CALLBACK_SYSTEM_ALL.to_string()))
+                    .await?;
+                return Ok(());
+            }
+            let nas = match target {
+                Some(nas) => nas,
+                None => nas_units.selected(msg.chat.id).await,
+            };
+
+            match load_system_status(nas, msg.chat.id).await {
+                Ok((info, util)) => {
+                    bot.send_message(msg.chat.id, format!("{}{}", nas.prefix, format_system_status(&info, &util)))
+                        .reply_markup(create_system_menu(nas_callback(CALLBACK_SYSTEM, &nas.name)))
+                        .await?;
                 },
-                Ok(false) => {
-                    bot.send_message(msg.chat.id, "Could not login to Synology NAS. Please check your SYNOLOGY_USERNAME and SYNOLOGY_PASSWORD environment variables.").await?;
+                Err(e) => {
+                    error!("Failed to load system status of {}: {}", nas.name, e);
+                    bot.send_message(msg.chat.id, format!("{}{}", nas.prefix, e)).await?;
+                }
+            }
+        }
+        Command::Files(arg) => {
+            // "/files", "/files photo/2024", "/files backup /photo"
+            let (nas, path) = nas_units.target(msg.chat.id, &arg).await;
+            let path = path.trim_end_matches('/');
+            let path = if path.is_empty() || path.starts_with('/') { path.to_string() } else { format!("/{}", path) };
+
+            match files::browse(nas, &nas_units.file_tokens, &path, 0, msg.chat.id).await {
+                Ok((text, menu)) => {
+                    bot.send_message(msg.chat.id, text).reply_markup(menu).await?;
                 },
                 Err(e) => {
-                    bot.send_message(msg.chat.id, format!("Failed to login to Synology NAS: {}", e)).await?;
+                    error!("Failed to browse {} on {}: {}", path, nas.name, e);
+                    bot.send_message(msg.chat.id, format!("{}{}", nas.prefix, e)).await?;
                 }
             }
         }
+        Command::Reboot(ref arg) | Command::Shutdown(ref arg) => {
+            let action = if matches!(cmd, Command::Reboot(_)) { PowerAction::Reboot } else { PowerAction::Shutdown };
+            let (target, rest) = nas_units.split_target(arg);
+            if !rest.is_empty() {
+                bot.send_message(msg.chat.id, nas_units.unknown_text(rest)).await?;
+                return Ok(());
+            }
+            let nas = match target {
+                Some(nas) => nas,
+                None => nas_units.selected(msg.chat.id).await,
+            };
+
+            let preflight = power::preflight_text(nas, msg.chat.id).await;
+            let pending = PendingPowerAction { action, nas: nas.name.clone() };
+            let nonce = power_confirmations.lock().await.create(pending, msg.chat.id, POWER_CONFIRM_TIMEOUT);
+            bot.send_message(
+                msg.chat.id,
+                format!(
+                    "⚠️ {}{} the NAS?\n\n{}\n\nThe buttons expire in {}.",
+                    nas.prefix,
+                    if action == PowerAction::Reboot { "Reboot" } else { "Shut down" },
+                    preflight,
+                    format_duration(POWER_CONFIRM_TIMEOUT)
+                )
+            )
+            .reply_markup(create_power_confirm_menu(action, &nonce))
+            .await?;
+        }
+        Command::Wake(arg) => {
+            let (target, rest) = nas_units.split_target(&arg);
+            if !rest.is_empty() {
+                bot.send_message(msg.chat.id, nas_units.unknown_text(rest)).await?;
+                return Ok(());
+            }
+            let nas = match target {
+                Some(nas) => nas,
+                None => nas_units.selected(msg.chat.id).await,
+            };
+            let Some(wol) = &nas.wol else {
+                bot.send_message(
+                    msg.chat.id,
+                    format!("{}Wake-on-LAN is not configured. Set STB_SYNOLOGY_WOL_MAC (STB_NAS_<NAME>_WOL_MAC for further NAS profiles).", nas.prefix)
+                ).await?;
+                return Ok(());
+            };
+            if power::dsm_answers(&nas.synology, msg.chat.id).await {
+                bot.send_message(msg.chat.id, format!("{}NAS is already up.", nas.prefix)).await?;
+                return Ok(());
+            }
+
+            if let Err(e) = wol.wake().await {
+                error!("Failed to send Wake-on-LAN packet to {}: {}", wol, e);
+                bot.send_message(msg.chat.id, format!("{}Failed to send the magic packet to {}: {}", nas.prefix, wol, e)).await?;
+                return Ok(());
+            }
+            info!("Wake-on-LAN packet sent to {} for NAS {}", wol, nas.name);
+            let progress = bot.send_message(msg.chat.id, format!("⏰ {}Magic packet sent to {}, waiting for DSM...", nas.prefix, wol)).await?;
+            power::spawn_wake_watch(bot.clone(), msg.chat.id, progress.id, nas.synology.clone(), nas.prefix.clone());
+        }
         Command::SealStatus => {
             match bao_config.as_ref() {
                 None => {
                     bot.send_message(msg.chat.id, "OpenBao support is not configured (STB_BAO_* env vars missing).").await?;
                 },
                 Some(bao) => {
-                    match bao.client.seal_status().await {
-                        Ok(status) => {
-                            let text = if !status.initialized {
-                                "OpenBao is NOT INITIALIZED.".to_string()
-                            } else if status.sealed {
-                                format!("🔒 OpenBao is SEALED (progress {}/{})", status.progress, status.t)
-                            } else {
-                                "🔓 OpenBao is unsealed.".to_string()
-                            };
-                            bot.send_message(msg.chat.id, text).await?;
+                    let nodes = bao.cluster.node_status().await;
+                    bot.send_message(msg.chat.id, format_seal_status(&nodes)).await?;
+                }
+            }
+        }
+        Command::Unseal => {
+            start_unseal_session(&bot, msg.chat.id, &bao_config, &unseal_sessions).await?;
+        }
+        Command::Seal => {
+            match bao_config.as_ref() {
+                None => {
+                    bot.send_message(msg.chat.id, "OpenBao support is not configured (STB_BAO_* env vars missing).").await?;
+                },
+                Some(bao) if !bao.cluster.has_auth() => {
+                    bot.send_message(msg.chat.id, "Sealing needs an OpenBao token (STB_BAO_TOKEN_FILE or STB_BAO_ROLE_ID/STB_BAO_SECRET_ID).").await?;
+                },
+                Some(bao) => {
+                    let nonce = bao.seal_confirmations.lock().await.create((), msg.chat.id, SEAL_CONFIRM_TIMEOUT);
+                    let sent = bot.send_message(
+                        msg.chat.id,
+                        format!(
+                            "⚠️ Seal OpenBao on all nodes?\n\nEvery client loses access to its secrets until the key holders unseal it again with /unseal.\n\nThe buttons expire in {}.",
+                            format_duration(SEAL_CONFIRM_TIMEOUT)
+                        )
+                    )
+                    .reply_markup(create_seal_confirm_menu(&nonce))
+                    .await?;
+
+                    // Take the buttons away once the confirmation has expired unused
+                    let bao_config = bao_config.clone();
+                    tokio::spawn(async move {
+                        tokio::time::sleep(SEAL_CONFIRM_TIMEOUT).await;
+                        let Some(bao) = bao_config.as_ref() else { return };
+                        if bao.seal_confirmations.lock().await.discard(&nonce)
+                            && let Err(e) = bot.edit_message_text(sent.chat.id, sent.id, "Seal confirmation expired, nothing was done. Run /seal again.").await
+                        {
+                            warn!("Failed to remove expired seal buttons: {}", e);
+                        }
+                    });
+                }
+            }
+        }
+        Command::BaoStepdown => {
+            match bao_config.as_ref() {
+                None => {
+                    bot.send_message(msg.chat.id, "OpenBao support is not configured (STB_BAO_* env vars missing).").await?;
+                },
+                Some(bao) => {
+                    match bao.cluster.step_down().await {
+                        Ok(addr) => {
+                            info!("OpenBao node {} stepped down on request of chat {}", addr, msg.chat.id.0);
+                            // Give the standbys a moment to elect the new leader
+                            tokio::time::sleep(Duration::from_secs(3)).await;
+                            let nodes = bao.cluster.node_status().await;
+                            bot.send_message(msg.chat.id, format!("⬇️ {} stepped down.\n\n{}", addr, format_seal_status(&nodes))).await?;
                         },
                         Err(e) => {
-                            bot.send_message(msg.chat.id, format!("Failed to get seal status: {}", e)).await?;
+                            error!("OpenBao step-down failed: {}", e);
+                            bot.send_message(msg.chat.id, format!("Step-down failed: {}", e)).await?;
                         }
                     }
                 }
             }
         }
-        Command::Unseal => {
+        Command::BaoHealth => {
             match bao_config.as_ref() {
                 None => {
                     bot.send_message(msg.chat.id, "OpenBao support is not configured (STB_BAO_* env vars missing).").await?;
                 },
                 Some(bao) => {
-                    match bao.client.seal_status().await {
-                        Ok(status) => {
-                            if !status.initialized {
-                                bot.send_message(msg.chat.id, "OpenBao is not initialized yet — unseal not possible.").await?;
-                            } else if !status.sealed {
-                                bot.send_message(msg.chat.id, "OpenBao is already unsealed.").await?;
-                            } else {
-                                let key = generate_ephemeral_key();
-                                let session_id = random_session_id();
-                                let link = format!("{}/#s={}&k={}", bao.web_url, session_id, key.recipient);
-                                info!("Unseal session {} created (chat {})", session_id, msg.chat.id.0);
-                                *pending_unseal.lock().await = Some(UnsealSession {
-                                    chat_id: msg.chat.id,
-                                    session_id: session_id.clone(),
-                                    identity: key.identity,
-                                    since: Instant::now(),
-                                });
-                                bot.send_message(
-                                    msg.chat.id,
-                                    format!("🔑 Open this link (valid for {} minutes), paste your unseal token from Bitwarden, encrypt it, and send the ciphertext back here:\n\n{}", UNSEAL_SESSION_TIMEOUT.as_secs() / 60, link)
-                                ).await?;
+                    let nodes = bao.cluster.node_status().await;
+                    let (autopilot, token) = if bao.cluster.has_auth() {
+                        (Some(bao.cluster.autopilot_state().await), Some(bao.cluster.token_info().await))
+                    } else {
+                        (None, None)
+                    };
+                    bot.send_message(msg.chat.id, format_bao_health(&nodes, autopilot.as_ref(), token.as_ref())).await?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// Open a collective unseal session in a chat; used by /unseal and the "Unseal now" button.
+// Key holders join with the button and get their personal link in a private chat.
+async fn start_unseal_session(
+    bot: &Bot,
+    chat_id: ChatId,
+    bao_config: &Option<BaoConfig>,
+    unseal_sessions: &Mutex<UnsealSessions>
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    match bao_config {
+        None => {
+            bot.send_message(chat_id, "OpenBao support is not configured (STB_BAO_* env vars missing).").await?;
+        },
+        Some(bao) => {
+            match bao.cluster.seal_status().await {
+                Ok(status) => {
+                    if !status.initialized {
+                        bot.send_message(chat_id, "OpenBao is not initialized yet — unseal not possible.").await?;
+                    } else if !status.sealed {
+                        bot.send_message(chat_id, "OpenBao is already unsealed.").await?;
+                    } else {
+                        // The chat's running session is shown again instead of being replaced
+                        let running = unseal_sessions
+                            .lock()
+                            .await
+                            .for_chat_mut(chat_id)
+                            .map(|session| (session.session_id.clone(), session.status_text()));
+                        if let Some((session_id, text)) = running {
+                            let sent = bot.send_message(chat_id, text)
+                                .reply_markup(unseal::create_unseal_menu(&session_id))
+                                .await?;
+                            if let Some(session) = unseal_sessions.lock().await.get_mut(&session_id) {
+                                session.status_message = Some(sent.id);
                             }
-                        },
-                        Err(e) => {
-                            bot.send_message(msg.chat.id, format!("Cannot reach OpenBao: {}", e)).await?;
+                            return Ok(());
                         }
+
+                        let mut session = UnsealSession::new(chat_id, status.progress, status.t, UNSEAL_SESSION_TIMEOUT);
+                        info!("Unseal session {} created (chat {}, threshold {})", session.session_id, chat_id.0, status.t);
+                        let sent = bot.send_message(chat_id, session.status_text())
+                            .reply_markup(unseal::create_unseal_menu(&session.session_id))
+                            .await?;
+                        session.status_message = Some(sent.id);
+                        unseal_sessions.lock().await.insert(session);
                     }
+                },
+                Err(e) => {
+                    bot.send_message(chat_id, format!("Cannot reach OpenBao: {}", e)).await?;
                 }
             }
         }
@@ -425,7 +1494,7 @@ async fn answer_command(
 async fn inline_query_handler(
     bot: Bot,
     q: InlineQuery,
-    _synology_config: Arc<Mutex<SynologyConfig>>
+    _nas_units: Arc<NasUnits>
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
     // Create a simple text result
     let result = InlineQueryResultArticle::new(
@@ -447,31 +1516,18 @@ async fn inline_query_handler(
         .await?;
 
     Ok(())
-}
-
-// Handle callback queries from inline keyboards
-async fn callback_handler(
-    bot: Bot,
-    q: CallbackQuery,
-    synology_config: Arc<Mutex<SynologyConfig>>
-) -> Result<(), Box<dyn Error + Send + Sync>> {
-    // If the callback query has a message, check if the chat is authorized
-    if let Some(message) = &q.message {
-        if !is_authorized_chat(message.chat.id.0) {
-            let first_name = q.from.first_name.clone();
-            let chat_id = message.chat.id.0;
-
-            warn!("Unauthorized callback query from user {} with chat ID {}", first_name, chat_id);
-
-            // Answer the callback query with an error message
-            bot.answer_callback_query(q.id)
-                .text(format!("You ({}) are not authorized to use this bot. Your chat ID {} is not allowed.", first_name, chat_id))
-                .show_alert(true)
-                .await?;
-
-            return Ok(());
-        }
-    }
+}
+
+// Handle callback queries from inline keyboards
+async fn callback_handler(
+    bot: Bot,
+    q: CallbackQuery,
+    nas_units: Arc<NasUnits>,
+    bao_config: Arc<Option<BaoConfig>>,
+    unseal_sessions: Arc<Mutex<UnsealSessions>>,
+    power_confirmations: Arc<Mutex<PowerConfirmations>>,
+    config: Arc<Config>
+) -> Result<(), Box<dyn Error + Send + Sync>> {
     // If the callback query has no data, return
     if let Some(data) = &q.data {
         // Get the message and chat ID
@@ -479,51 +1535,57 @@ async fn callback_handler(
             let chat_id = message.chat.id;
             info!("Callback '{}' received from chat {}", data, chat_id.0);
 
-            match data.as_str() {
+            // Buttons of one NAS carry its name ("ssh_on@backup"), others act on the selected NAS
+            let (data, nas) = match data.split_once('@') {
+                Some((data, name)) => match nas_units.get(name) {
+                    Some(nas) => (data, nas),
+                    None => {
+                        bot.answer_callback_query(q.id)
+                            .text(nas_units.unknown_text(name))
+                            .await?;
+                        return Ok(());
+                    }
+                },
+                None => (data.as_str(), nas_units.selected(chat_id).await),
+            };
+            let synology_config = &nas.synology;
+            let ssh_timer = &nas.ssh_timer;
+
+            match data {
                 // Main menu options
                 CALLBACK_SSH_MENU => {
                     // Get current SSH status before showing the menu
                     let mut config = synology_config.lock().await;
 
                     // Ensure logged in
-                    match config.ensure_logged_in().await {
-                        Ok(true) => {
-                            // Now we're logged in, proceed with getting SSH status
-                            if let Some(client) = &mut config.client {
-                                match client.get_ssh_status().await {
-                                    Ok(status) => {
-                                        // Create SSH menu based on current status
-                                        let keyboard = create_ssh_menu(status);
-                                        let status_text = if status { "enabled" } else { "disabled" };
-
-                                        bot.edit_message_text(
-                                            chat_id,
-                                            message.id,
-                                            format!("SSH Control Menu (currently {})", status_text)
-                                        )
-                                        .reply_markup(keyboard)
-                                        .await?;
-                                    },
-                                    Err(e) => {
-                                        error!("Failed to get SSH status: {}", e);
-                                        bot.answer_callback_query(q.id)
-                                            .text("Failed to get SSH status")
-                                            .show_alert(true)
-                                            .await?;
-                                    }
-                                }
-                            }
-                        },
-                        Ok(false) => {
+                    let client = match config.logged_in_client(chat_id).await {
+                        Ok(client) => client,
+                        Err(e) => {
                             bot.answer_callback_query(q.id)
-                                .text("Could not login to Synology NAS. Please check your SYNOLOGY_USERNAME and SYNOLOGY_PASSWORD environment variables.")
+                                .text(e)
                                 .show_alert(true)
                                 .await?;
+                            return Ok(());
+                        }
+                    };
+                    match client.get_ssh_status().await {
+                        Ok(status) => {
+                            // Create SSH menu based on current status
+                            let keyboard = create_ssh_menu(status, &nas.name);
+                            let status_text = if status { "enabled" } else { "disabled" };
+
+                            bot.edit_message_text(
+                                chat_id,
+                                message.id,
+                                format!("{}SSH Control Menu (currently {})", nas.prefix, status_text)
+                            )
+                            .reply_markup(keyboard)
+                            .await?;
                         },
                         Err(e) => {
-                            error!("Failed to login: {}", e);
+                            error!("Failed to get SSH status: {}", e);
                             bot.answer_callback_query(q.id)
-                                .text("Failed to login to Synology NAS")
+                                .text("Failed to get SSH status")
                                 .show_alert(true)
                                 .await?;
                         }
@@ -534,46 +1596,37 @@ async fn callback_handler(
                     let mut config = synology_config.lock().await;
 
                     // Ensure logged in
-                    match config.ensure_logged_in().await {
-                        Ok(true) => {
-                            // Now we're logged in, proceed with enabling SSH
-                            if let Some(client) = &mut config.client {
-                                match client.toggle_ssh(true).await {
-                                    Ok(_) => {
-                                        bot.answer_callback_query(q.id)
-                                            .text("SSH service has been enabled")
-                                            .await?;
-
-                                        // Return to main menu
-                                        let keyboard = create_main_menu();
-                                        bot.edit_message_text(
-                                            chat_id,
-                                            message.id,
-                                            "SSH service has been enabled. Please select an option from the menu below:"
-                                        )
-                                        .reply_markup(keyboard)
-                                        .await?;
-                                    },
-                                    Err(e) => {
-                                        error!("Failed to toggle ssh service: {}", e);
-                                        bot.answer_callback_query(q.id)
-                                            .text("Failed to enable SSH service")
-                                            .show_alert(true)
-                                            .await?;
-                                    }
-                                }
-                            }
-                        },
-                        Ok(false) => {
+                    let client = match config.logged_in_client(chat_id).await {
+                        Ok(client) => client,
+                        Err(e) => {
                             bot.answer_callback_query(q.id)
-                                .text("Could not login to Synology NAS. Please check your SYNOLOGY_USERNAME and SYNOLOGY_PASSWORD environment variables.")
+                                .text(e)
                                 .show_alert(true)
                                 .await?;
+                            return Ok(());
+                        }
+                    };
+                    match client.toggle_ssh(true).await {
+                        Ok(_) => {
+                            ssh_timer.cancel().await;
+                            bot.answer_callback_query(q.id)
+                                .text("SSH service has been enabled")
+                                .await?;
+
+                            // Return to main menu
+                            let keyboard = nas_units.main_menu(chat_id).await;
+                            bot.edit_message_text(
+                                chat_id,
+                                message.id,
+                                format!("{}SSH service has been enabled. Please select an option from the menu below:", nas.prefix)
+                            )
+                            .reply_markup(keyboard)
+                            .await?;
                         },
                         Err(e) => {
                             error!("Failed to toggle ssh service: {}", e);
                             bot.answer_callback_query(q.id)
-                                .text("Failed to login to Synology NAS")
+                                .text("Failed to enable SSH service")
                                 .show_alert(true)
                                 .await?;
                         }
@@ -584,59 +1637,469 @@ async fn callback_handler(
                     let mut config = synology_config.lock().await;
 
                     // Ensure logged in
-                    match config.ensure_logged_in().await {
-                        Ok(true) => {
-                            // Now we're logged in, proceed with disabling SSH
-                            if let Some(client) = &mut config.client {
-                                match client.toggle_ssh(false).await {
-                                    Ok(_) => {
-                                        bot.answer_callback_query(q.id)
-                                            .text("SSH service has been disabled")
-                                            .await?;
-
-                                        // Return to main menu
-                                        let keyboard = create_main_menu();
-                                        bot.edit_message_text(
-                                            chat_id,
-                                            message.id,
-                                            "SSH service has been disabled. Please select an option from the menu below:"
-                                        )
-                                        .reply_markup(keyboard)
-                                        .await?;
-                                    },
-                                    Err(e) => {
-                                        bot.answer_callback_query(q.id)
-                                            .text(format!("Failed to disable SSH service: {}", e))
-                                            .show_alert(true)
-                                            .await?;
-                                    }
-                                }
+                    let client = match config.logged_in_client(chat_id).await {
+                        Ok(client) => client,
+                        Err(e) => {
+                            bot.answer_callback_query(q.id)
+                                .text(e)
+                                .show_alert(true)
+                                .await?;
+                            return Ok(());
+                        }
+                    };
+                    match client.toggle_ssh(false).await {
+                        Ok(_) => {
+                            ssh_timer.cancel().await;
+                            bot.answer_callback_query(q.id)
+                                .text("SSH service has been disabled")
+                                .await?;
+
+                            // Return to main menu
+                            let keyboard = nas_units.main_menu(chat_id).await;
+                            bot.edit_message_text(
+                                chat_id,
+                                message.id,
+                                format!("{}SSH service has been disabled. Please select an option from the menu below:", nas.prefix)
+                            )
+                            .reply_markup(keyboard)
+                            .await?;
+                        },
+                        Err(e) => {
+                            bot.answer_callback_query(q.id)
+                                .text(format!("Failed to disable SSH service: {}", e))
+                                .show_alert(true)
+                                .await?;
+                        }
+                    }
+                }
+                CALLBACK_SERVICES => {
+                    // Show the live state of every known service
+                    let mut config = synology_config.lock().await;
+
+                    let client = match config.logged_in_client(chat_id).await {
+                        Ok(client) => client,
+                        Err(e) => {
+                            bot.answer_callback_query(q.id)
+                                .text(e)
+                                .show_alert(true)
+                                .await?;
+                            return Ok(());
+                        }
+                    };
+                    match client.get_all_service_status().await {
+                        Ok(statuses) => {
+                            bot.edit_message_text(
+                                chat_id,
+                                message.id,
+                                format!("{}Services Menu\n\n{}", nas.prefix, format_service_statuses(&statuses))
+                            )
+                            .reply_markup(create_services_menu(&statuses, &nas.name))
+                            .await?;
+                        },
+                        Err(e) => {
+                            error!("Failed to get service status: {}", e);
+                            bot.answer_callback_query(q.id)
+                                .text("Failed to get service status")
+                                .show_alert(true)
+                                .await?;
+                        }
+                    }
+                }
+                d if d.starts_with(CALLBACK_SERVICE_ON) || d.starts_with(CALLBACK_SERVICE_OFF) => {
+                    let enable = d.starts_with(CALLBACK_SERVICE_ON);
+                    let name = d.split_once(':').map(|(_, name)| name).unwrap_or_default();
+                    let Some(service) = find_service(name) else {
+                        bot.answer_callback_query(q.id)
+                            .text("Unknown service")
+                            .await?;
+                        return Ok(());
+                    };
+                    let action_text = if enable { "enable" } else { "disable" };
+
+                    let mut config = synology_config.lock().await;
+
+                    let client = match config.logged_in_client(chat_id).await {
+                        Ok(client) => client,
+                        Err(e) => {
+                            bot.answer_callback_query(q.id)
+                                .text(e)
+                                .show_alert(true)
+                                .await?;
+                            return Ok(());
+                        }
+                    };
+                    match client.set_service(service, enable).await {
+                        Ok(_) => {
+                            info!("{} service {}d by chat {}", service.label, action_text, chat_id.0);
+                            if service.name == SSH_SERVICE.name {
+                                ssh_timer.cancel().await;
                             }
+                            bot.answer_callback_query(q.id)
+                                .text(format!("{} service has been {}d", service.label, action_text))
+                                .await?;
+
+                            // Refresh the services menu
+                            match client.get_all_service_status().await {
+                                Ok(statuses) => {
+                                    bot.edit_message_text(
+                                        chat_id,
+                                        message.id,
+                                        format!("{}Services Menu\n\n{}", nas.prefix, format_service_statuses(&statuses))
+                                    )
+                                    .reply_markup(create_services_menu(&statuses, &nas.name))
+                                    .await?;
+                                },
+                                Err(e) => error!("Failed to refresh service status: {}", e),
+                            }
+                        },
+                        Err(e) => {
+                            error!("Failed to {} {} service: {}", action_text, service.label, e);
+                            bot.answer_callback_query(q.id)
+                                .text(format!("Failed to {} {} service: {}", action_text, service.label, e))
+                                .show_alert(true)
+                                .await?;
+                        }
+                    }
+                }
+                CALLBACK_STORAGE => {
+                    let mut config = synology_config.lock().await;
+
+                    let client = match config.logged_in_client(chat_id).await {
+                        Ok(client) => client,
+                        Err(e) => {
+                            bot.answer_callback_query(q.id)
+                                .text(e)
+                                .show_alert(true)
+                                .await?;
+                            return Ok(());
+                        }
+                    };
+                    match client.get_storage_info().await {
+                        Ok(info) => {
+                            bot.edit_message_text(chat_id, message.id, format!("{}{}", nas.prefix, format_storage_info(&info)))
+                                .reply_markup(create_storage_menu(&nas.name))
+                                .await?;
+                        },
+                        Err(e) => {
+                            error!("Failed to load storage info: {}", e);
+                            bot.answer_callback_query(q.id)
+                                .text(format!("Failed to load storage info: {}", e))
+                                .show_alert(true)
+                                .await?;
+                        }
+                    }
+                }
+                CALLBACK_UNSEAL => {
+                    // "Unseal now" from the seal watchdog: same as /unseal
+                    bot.answer_callback_query(q.id).await?;
+                    start_unseal_session(&bot, chat_id, &bao_config, &unseal_sessions).await?;
+                }
+                d if d.starts_with(CALLBACK_UNSEAL_JOIN) => {
+                    let session_id = &d[CALLBACK_UNSEAL_JOIN.len()..];
+                    let Some(bao) = bao_config.as_ref() else {
+                        bot.answer_callback_query(q.id).text("OpenBao support is not configured.").await?;
+                        return Ok(());
+                    };
+                    // The store stays locked only for the join, not while Telegram is called
+                    let joined = unseal_sessions
+                        .lock()
+                        .await
+                        .get_mut(session_id)
+                        .map(|session| (session.join(q.from.id, &q.from.full_name()), session.remaining()));
+                    let Some((link, remaining)) = joined else {
+                        bot.answer_callback_query(q.id)
+                            .text("This unseal session is over. Call /unseal for a new one.")
+                            .show_alert(true)
+                            .await?;
+                        return Ok(());
+                    };
+                    let Some(link) = link else {
+                        bot.answer_callback_query(q.id)
+                            .text("You have already submitted your share.")
+                            .show_alert(true)
+                            .await?;
+                        return Ok(());
+                    };
+
+                    // The link goes to the key holder's private chat, never to the group
+                    let url = format!("{}/#s={}&k={}", bao.web_url, link.share_id, link.recipient);
+                    let text = format!("🔑 Your personal unseal link (valid for {}). Open it, paste your unseal key share, encrypt it, and send the ciphertext back here:\n\n{}", format_duration(remaining), url);
+                    let sent = match unseal::create_share_keyboard(&bao.web_url, &link.recipient) {
+                        Some(keyboard) => bot.send_message(q.from.id, format!("{}\n\nOr tap the button below to enter the share right here in Telegram.", text))
+                            .reply_markup(keyboard)
+                            .await,
+                        None => bot.send_message(q.from.id, text).await,
+                    };
+                    match sent {
+                        Ok(_) => {
+                            info!("Unseal session {}: link issued to user {}", session_id, q.from.id.0);
+                            bot.answer_callback_query(q.id).text("Your link has been sent to you in a private chat.").await?;
+                            let update = unseal_sessions.lock().await.get_mut(session_id).and_then(|session| session.status_update());
+                            if let Some(update) = update {
+                                unseal::update_unseal_status(&bot, update).await;
+                            }
+                        },
+                        Err(e) => {
+                            warn!("Unseal session {}: cannot send link to user {}: {}", session_id, q.from.id.0, e);
+                            bot.answer_callback_query(q.id)
+                                .text("I cannot message you privately. Please start a private chat with the bot first, then press the button again.")
+                                .show_alert(true)
+                                .await?;
+                        }
+                    }
+                }
+                d if d.starts_with(CALLBACK_SEAL_CONFIRM) => {
+                    let Some(bao) = bao_config.as_ref() else {
+                        bot.answer_callback_query(q.id).text("OpenBao support is not configured.").await?;
+                        return Ok(());
+                    };
+                    if bao.seal_confirmations.lock().await.take(&d[CALLBACK_SEAL_CONFIRM.len()..], chat_id).is_none() {
+                        bot.answer_callback_query(q.id).text("This confirmation has expired.").await?;
+                        bot.edit_message_text(chat_id, message.id, "Seal confirmation expired, nothing was done. Run /seal again.").await?;
+                        return Ok(());
+                    }
+                    bot.answer_callback_query(q.id).text("Sealing OpenBao...").await?;
+                    // Remove the buttons first, so the confirmation cannot be pressed twice
+                    bot.edit_message_text(chat_id, message.id, "🔒 Sealing OpenBao...").await?;
+                    warn!("OpenBao seal requested by user {} in chat {}", q.from.id.0, chat_id.0);
+                    let text = match bao.cluster.seal_all().await {
+                        Ok(results) => {
+                            let text = results
+                                .iter()
+                                .map(|(addr, result)| match result {
+                                    Ok(status) if status.sealed => format!("🔒 {}: sealed", addr),
+                                    Ok(_) => format!("⚠️ {}: still unsealed", addr),
+                                    Err(e) => format!("❌ {}: {}", addr, e),
+                                })
+                                .collect::<Vec<_>>()
+                                .join("\n");
+                            format!("OpenBao seal by {}:\n\n{}\n\nUse /unseal to unseal it again.", q.from.full_name(), text)
+                        },
+                        Err(e) => {
+                            error!("OpenBao seal failed: {}", e);
+                            format!("Sealing failed: {}", e)
+                        }
+                    };
+                    bot.edit_message_text(chat_id, message.id, text).await?;
+                }
+                d if d.starts_with(CALLBACK_SEAL_CANCEL) => {
+                    if let Some(bao) = bao_config.as_ref() {
+                        bao.seal_confirmations.lock().await.take(&d[CALLBACK_SEAL_CANCEL.len()..], chat_id);
+                    }
+                    bot.answer_callback_query(q.id).await?;
+                    bot.edit_message_text(chat_id, message.id, "Seal cancelled.").await?;
+                }
+                d if d.starts_with(CALLBACK_FILES) => {
+                    let page = parse_page_callback(&d[CALLBACK_FILES.len()..]);
+                    let target = match page {
+                        Some((token, _)) => nas_units.file_tokens.lock().await.resolve(token),
+                        None => None,
+                    };
+                    let (Some((_, offset)), Some(target)) = (page, target) else {
+                        bot.answer_callback_query(q.id).text("This listing is outdated, please run /files again.").show_alert(true).await?;
+                        return Ok(());
+                    };
+                    let Some(nas) = nas_units.get(&target.nas) else {
+                        bot.answer_callback_query(q.id).text(nas_units.unknown_text(&target.nas)).await?;
+                        return Ok(());
+                    };
+                    match files::browse(nas, &nas_units.file_tokens, &target.path, offset, chat_id).await {
+                        Ok((text, menu)) => {
+                            bot.answer_callback_query(q.id).await?;
+                            match bot.edit_message_text(chat_id, message.id, text).reply_markup(menu).await {
+                                Ok(_) | Err(RequestError::Api(ApiError::MessageNotModified)) => {},
+                                Err(e) => return Err(e.into()),
+                            }
+                        },
+                        Err(e) => {
+                            error!("Failed to browse {} on {}: {}", target.path, nas.name, e);
+                            bot.answer_callback_query(q.id).text(e).show_alert(true).await?;
+                        }
+                    }
+                }
+                d if d.starts_with(CALLBACK_POWER_CANCEL) => {
+                    power_confirmations.lock().await.take(&d[CALLBACK_POWER_CANCEL.len()..], chat_id);
+                    bot.answer_callback_query(q.id).await?;
+                    bot.edit_message_text(chat_id, message.id, "Cancelled.").await?;
+                }
+                d if d.starts_with(CALLBACK_POWER_CONFIRM) => {
+                    let pending = power_confirmations.lock().await.take(&d[CALLBACK_POWER_CONFIRM.len()..], chat_id);
+                    let Some(pending) = pending else {
+                        bot.answer_callback_query(q.id).text("This confirmation has expired.").await?;
+                        bot.edit_message_text(chat_id, message.id, "Confirmation expired, nothing was done. Run /reboot or /shutdown again.").await?;
+                        return Ok(());
+                    };
+                    let Some(nas) = nas_units.get(&pending.nas) else {
+                        bot.answer_callback_query(q.id).text(nas_units.unknown_text(&pending.nas)).await?;
+                        return Ok(());
+                    };
+                    let reboot = pending.action == PowerAction::Reboot;
+                    bot.answer_callback_query(q.id).await?;
+                    warn!("NAS {} {} requested by user {} in chat {}", nas.name, pending.action.verb(), q.from.id.0, chat_id.0);
+
+                    let result = {
+                        let mut config = nas.synology.lock().await;
+                        match config.logged_in_client(chat_id).await {
+                            Ok(client) => client.power_off(reboot).await.map_err(|e| e.to_string()),
+                            Err(e) => Err(e),
+                        }
+                    };
+                    match result {
+                        Ok(()) if reboot => {
+                            bot.edit_message_text(chat_id, message.id, format!("🔁 {}Reboot requested by {}, waiting for DSM to go down...", nas.prefix, q.from.full_name())).await?;
+                            power::spawn_reboot_watch(bot.clone(), chat_id, message.id, nas.synology.clone(), nas.prefix.clone());
+                        },
+                        Ok(()) => {
+                            // The session ends with the NAS
+                            nas.synology.lock().await.client = None;
+                            let wake_hint = if nas.wol.is_some() { " Use /wake to power it on again." } else { "" };
+                            bot.edit_message_text(chat_id, message.id, format!("⏻ {}Shutdown requested by {}. The NAS is powering off.{}", nas.prefix, q.from.full_name(), wake_hint)).await?;
                         },
-                        Ok(false) => {
+                        Err(e) => {
+                            error!("Failed to {} {}: {}", pending.action.verb(), nas.name, e);
+                            bot.edit_message_text(chat_id, message.id, format!("{}Failed to {} the NAS: {}", nas.prefix, pending.action.verb(), e)).await?;
+                        }
+                    }
+                }
+                CALLBACK_SSH_EXTEND => {
+                    match ssh_timer.extend().await {
+                        Some(_) => {
+                            let left = ssh_timer.remaining().await.unwrap_or_default();
+                            bot.answer_callback_query(q.id)
+                                .text(format!("SSH stays enabled for another {}", format_duration(left)))
+                                .await?;
+                            bot.edit_message_text(
+                                chat_id,
+                                message.id,
+                                format!("⏱️ {}SSH auto-disable extended, SSH is disabled in {}.", nas.prefix, format_duration(left))
+                            ).await?;
+                        },
+                        None => {
+                            bot.answer_callback_query(q.id)
+                                .text("No automatic SSH disable is pending")
+                                .show_alert(true)
+                                .await?;
+                        }
+                    }
+                }
+                d if d.starts_with(CALLBACK_SSH_ON_FOR) => {
+                    // Timed enable from the SSH menu
+                    let duration = d[CALLBACK_SSH_ON_FOR.len()..]
+                        .parse::<u64>()
+                        .ok()
+                        .and_then(|minutes| parse_duration(&format!("{}m", minutes)));
+                    let Some(duration) = duration else {
+                        bot.answer_callback_query(q.id)
+                            .text("Invalid duration")
+                            .await?;
+                        return Ok(());
+                    };
+
+                    let mut config = synology_config.lock().await;
+
+                    // Ensure logged in
+                    let client = match config.logged_in_client(chat_id).await {
+                        Ok(client) => client,
+                        Err(e) => {
                             bot.answer_callback_query(q.id)
-                                .text("Could not login to Synology NAS. Please check your SYNOLOGY_USERNAME and SYNOLOGY_PASSWORD environment variables.")
+                                .text(e)
                                 .show_alert(true)
                                 .await?;
+                            return Ok(());
+                        }
+                    };
+                    match client.toggle_ssh(true).await {
+                        Ok(_) => {
+                            info!("SSH service enabled for {} by chat {}", format_duration(duration), chat_id.0);
+                            ssh_timer.schedule(chat_id, duration).await;
+                            bot.answer_callback_query(q.id)
+                                .text(format!("SSH service has been enabled for {}", format_duration(duration)))
+                                .await?;
+
+                            // Return to main menu
+                            let keyboard = nas_units.main_menu(chat_id).await;
+                            bot.edit_message_text(
+                                chat_id,
+                                message.id,
+                                format!("{}SSH service has been enabled for {}. Please select an option from the menu below:", nas.prefix, format_duration(duration))
+                            )
+                            .reply_markup(keyboard)
+                            .await?;
                         },
                         Err(e) => {
+                            error!("Failed to toggle ssh service: {}", e);
                             bot.answer_callback_query(q.id)
-                                .text(format!("Failed to login to Synology NAS: {}", e))
+                                .text("Failed to enable SSH service")
                                 .show_alert(true)
                                 .await?;
                         }
                     }
                 }
+                CALLBACK_SYSTEM | CALLBACK_SYSTEM_ALL => {
+                    let (text, refresh) = if data == CALLBACK_SYSTEM_ALL {
+                        (system_overview_text(&nas_units, chat_id).await, CALLBACK_SYSTEM_ALL.to_string())
+                    } else {
+                        match load_system_status(nas, chat_id).await {
+                            Ok((info, util)) => (
+                                format!("{}{}", nas.prefix, format_system_status(&info, &util)),
+                                nas_callback(CALLBACK_SYSTEM, &nas.name)
+                            ),
+                            Err(e) => {
+                                error!("Failed to load system status of {}: {}", nas.name, e);
+                                bot.answer_callback_query(q.id)
+                                    .text(e)
+                                    .show_alert(true)
+                                    .await?;
+                                return Ok(());
+                            }
+                        }
+                    };
+                    bot.answer_callback_query(q.id).await?;
+                    // Refresh edits the message in place; an unchanged load is not an error
+                    match bot.edit_message_text(chat_id, message.id, text)
+                        .reply_markup(create_system_menu(refresh))
+                        .await
+                    {
+                        Ok(_) | Err(RequestError::Api(ApiError::MessageNotModified)) => {},
+                        Err(e) => return Err(e.into()),
+                    }
+                }
                 CALLBACK_SETTINGS => {
-                    // Inform user that settings can only be configured via environment variables
+                    // Inform user that settings can only be configured in the config file or environment
+                    let source = match &config.file {
+                        Some(file) => format!("the config file {} and environment variables", file.display()),
+                        None => "environment variables".to_string(),
+                    };
+                    let units: Vec<String> = config.nas.iter().map(|nas| format!("{} at {}", nas.name, nas.base_url)).collect();
                     bot.send_message(
                         chat_id,
-                        "Synology settings must be configured via environment variable SYNOLOGY_NAS_BASE_URL. It cannot be changed via Telegram."
+                        format!("The bot talks to the Synology NAS {}. Settings come from {} and cannot be changed via Telegram.", units.join(", "), source)
                     ).await?;
                 }
+                d if d.starts_with(CALLBACK_NAS_SELECT) => {
+                    match nas_units.select(chat_id, &d[CALLBACK_NAS_SELECT.len()..]).await {
+                        Some(nas) => {
+                            bot.answer_callback_query(q.id)
+                                .text(format!("{} selected", nas.name))
+                                .await?;
+                            bot.edit_message_text(
+                                chat_id,
+                                message.id,
+                                format!("NAS {} selected. Please select an option from the menu below:", nas.name)
+                            )
+                            .reply_markup(nas_units.main_menu(chat_id).await)
+                            .await?;
+                        },
+                        None => {
+                            bot.answer_callback_query(q.id)
+                                .text("Unknown NAS")
+                                .await?;
+                        }
+                    }
+                }
                 CALLBACK_BACK => {
                     // Return to main menu
-                    let keyboard = create_main_menu();
+                    let keyboard = nas_units.main_menu(chat_id).await;
                     bot.edit_message_text(
                         chat_id,
                         message.id,
@@ -657,101 +2120,119 @@ async fn callback_handler(
     Ok(())
 }
 
+// A 2-step verification code as sent by the user: six digits, spaces allowed ("123 456")
+fn parse_otp_code(text: &str) -> Option<String> {
+    let code: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+    (code.len() == 6 && code.chars().all(|c| c.is_ascii_digit())).then_some(code)
+}
+
+// An encrypted share is a few hundred bytes; anything much larger is not one
+const MAX_SHARE_FILE_BYTES: u32 = 64 * 1024;
+
+// Download an uploaded share file (binary or armored age)
+async fn download_share_file(bot: &Bot, document: &Document) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    if document.file.size > MAX_SHARE_FILE_BYTES {
+        return Err(format!("file is larger than {} KB", MAX_SHARE_FILE_BYTES / 1024).into());
+    }
+    let file = bot.get_file(&document.file.id).await?;
+    let mut data = Vec::new();
+    bot.download_file(&file.path, &mut data).await?;
+    Ok(data)
+}
+
 // Handle all messages
+// Pending unseal session: a key holder's next message is their age-encrypted share, as text,
+// as uploaded .age file or from the unseal Mini App. In groups only messages that are
+// recognizably a ciphertext are taken. Returns whether the message was a share.
+async fn submit_share_message(
+    bot: &Bot,
+    msg: &Message,
+    nas_units: &NasUnits,
+    bao_config: &Option<BaoConfig>,
+    unseal_sessions: &Mutex<UnsealSessions>
+) -> Result<bool, Box<dyn Error + Send + Sync>> {
+    let Some(user) = msg.from() else {
+        return Ok(false);
+    };
+    let awaiting = unseal_sessions.lock().await.awaiting_share_from(user.id).map(|s| s.session_id.clone());
+    if let Some(session_id) = awaiting {
+        let private = msg.chat.is_private();
+        let ciphertext = match (&msg.kind, msg.text(), msg.document()) {
+            // Mini App data never shows up in the chat, there is nothing to delete
+            (MessageKind::WebAppData(data), _, _) => Some((data.web_app_data.data.as_bytes().to_vec(), false)),
+            (_, Some(text), _) if private || text.trim().starts_with(AGE_ARMOR_HEADER) => {
+                Some((text.trim().as_bytes().to_vec(), true))
+            },
+            (_, _, Some(document)) if private || document.file_name.as_deref().is_some_and(|n| n.ends_with(".age")) => {
+                match download_share_file(bot, document).await {
+                    Ok(data) => Some((data, true)),
+                    Err(e) => {
+                        warn!("Unseal session {}: cannot download share file of user {}: {}", session_id, user.id.0, e);
+                        bot.send_message(msg.chat.id, format!("Could not read the uploaded file: {}", e)).await?;
+                        return Ok(true);
+                    }
+                }
+            },
+            _ => None,
+        };
+        if let Some((ciphertext, delete)) = ciphertext {
+            // Delete the message carrying the ciphertext (hygiene)
+            if delete && let Err(e) = bot.delete_message(msg.chat.id, msg.id).await {
+                warn!("Could not delete ciphertext message: {}", e);
+            }
+            if unseal::submit_share(bot, msg.chat.id, user.id, &session_id, &ciphertext, bao_config, unseal_sessions).await? {
+                nas_units.reload_credentials().await;
+            }
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
 async fn message_handler(
     bot: Bot,
     msg: Message,
-    synology_config: Arc<Mutex<SynologyConfig>>,
+    auth: Authorization,
+    nas_units: Arc<NasUnits>,
     bao_config: Arc<Option<BaoConfig>>,
-    pending_unseal: Arc<Mutex<Option<UnsealSession>>>
+    unseal_sessions: Arc<Mutex<UnsealSessions>>,
+    state_store: Arc<Mutex<StateStore>>
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
-    // Check if the chat is authorized
-    if !is_authorized_chat(msg.chat.id.0) {
-        let first_name = msg.from()
-            .map(|user| user.first_name.clone())
-            .unwrap_or_else(|| String::from("Unknown"));
-
-        warn!("Unauthorized access attempt from user {} with chat ID {}", first_name, msg.chat.id.0);
-
-        bot.send_message(
-            msg.chat.id,
-            format!("You ({}) are not authorized to use this bot. Your chat ID {} is not allowed.", first_name, msg.chat.id.0)
-        ).await?;
-
-        return Ok(());
-    }
-
-    // Pending unseal session: the next text message in this chat is the age ciphertext
-    {
-        let mut pending = pending_unseal.lock().await;
-        if let Some(p) = pending.as_ref() {
-            if p.chat_id == msg.chat.id {
-                if p.since.elapsed() > UNSEAL_SESSION_TIMEOUT {
-                    let sid = p.session_id.clone();
-                    *pending = None;
-                    info!("Unseal session {} expired (chat {})", sid, msg.chat.id.0);
-                    bot.send_message(msg.chat.id, "Unseal session expired. Call /unseal again for a new link.").await?;
-                    return Ok(());
+    // DSM asked for a 2-step verification code: a 6-digit message from an operator completes the login
+    // The code goes to the first NAS that waits for one in this chat
+    if let Some(code) = msg.text().and_then(parse_otp_code) && auth.granted >= Some(Role::Operator) {
+        for nas in &nas_units.units {
+            let mut config = nas.synology.lock().await;
+            let config = &mut *config;
+            if let Some(client) = config.client.as_mut() && client.otp_pending_in(msg.chat.id.0) {
+                // Delete the message carrying the code (hygiene)
+                if let Err(e) = bot.delete_message(msg.chat.id, msg.id).await {
+                    warn!("Could not delete 2-step verification code message: {}", e);
                 }
-                if let Some(text) = msg.text() {
-                    // Take the session out (single attempt; /unseal restarts with a fresh link)
-                    let session = pending.take().expect("session checked above");
-                    let ciphertext = text.trim().to_string();
-                    // Delete the message carrying the ciphertext (hygiene)
-                    if let Err(e) = bot.delete_message(msg.chat.id, msg.id).await {
-                        warn!("Could not delete ciphertext message: {}", e);
-                    }
-                    if !ciphertext.starts_with("-----BEGIN AGE ENCRYPTED FILE-----") {
-                        warn!("Unseal session {}: message is not an age ciphertext (chat {})", session.session_id, msg.chat.id.0);
-                        bot.send_message(msg.chat.id, "That was not an age ciphertext. Please encrypt the token in the web app and send the encrypted text. Call /unseal for a new link.").await?;
-                        return Ok(());
+                match client.login_with_otp(&code).await {
+                    Ok(device_id) => {
+                        info!("DSM 2-step verification of {} completed by chat {}, device token stored", nas.name, msg.chat.id.0);
+                        config.device_id = Some(device_id.clone());
+                        let key = nas.state_key.as_deref();
+                        state_store.lock().await.update(|s| s.nas_mut(key).synology_device_id = Some(device_id));
+                        bot.send_message(msg.chat.id, format!("✅ {}2-step verification succeeded. The device token is stored, later logins need no code. Please repeat your last command.", nas.prefix)).await?;
+                    },
+                    Err(e) => {
+                        warn!("DSM login of {} with 2-step verification code failed (chat {}): {}", nas.name, msg.chat.id.0, e);
+                        bot.send_message(msg.chat.id, format!("❌ {}Login with 2-step verification code failed: {}", nas.prefix, e)).await?;
                     }
-                    match bao_config.as_ref() {
-                        None => {
-                            bot.send_message(msg.chat.id, "OpenBao support is not configured.").await?;
-                        },
-                        Some(bao) => {
-                            match decrypt_ciphertext(&ciphertext, &session.identity) {
-                                Ok(key) => {
-                                    info!("Unseal session {}: ciphertext decrypted (chat {})", session.session_id, msg.chat.id.0);
-                                    match bao.client.unseal(key.trim()).await {
-                                        Ok(status) => {
-                                            if status.sealed {
-                                                error!("Unseal session {}: key accepted, still sealed (progress {}/{})", session.session_id, status.progress, status.t);
-                                                bot.send_message(msg.chat.id, format!("⚠️ Key accepted, still sealed (progress {}/{})", status.progress, status.t)).await?;
-                                            } else {
-                                                info!("Unseal session {}: OpenBao unsealed via Telegram (chat {})", session.session_id, msg.chat.id.0);
-                                                bot.send_message(msg.chat.id, "🔓 OpenBao is now unsealed.").await?;
-                                            }
-                                        },
-                                        Err(e) => {
-                                            error!("Unseal session {}: unseal API call failed: {}", session.session_id, e);
-                                            bot.send_message(msg.chat.id, format!("Unseal failed: {}", e)).await?;
-                                        }
-                                    }
-                                },
-                                Err(e) => {
-                                    warn!("Unseal session {}: decryption failed (chat {}): {}", session.session_id, msg.chat.id.0, e);
-                                    bot.send_message(msg.chat.id, format!("❌ Decryption failed: {}. Call /unseal for a new link.", e)).await?;
-                                }
-                            }
-                        }
-                    }
-                    return Ok(());
                 }
+                return Ok(());
             }
         }
     }
 
-    if let Some(text) = msg.text() {
-        // Try to parse as a command
-        if let Ok(command) = Command::parse(text, "synology_bot") {
-            return answer_command(bot.clone(), msg.clone(), command, synology_config.clone(), bao_config.clone(), pending_unseal.clone()).await;
-        }
+    if submit_share_message(&bot, &msg, &nas_units, &bao_config, &unseal_sessions).await? {
+        return Ok(());
+    }
 
+    if let Some(text) = msg.text() {
         // Handle custom commands
-
-
         if text.starts_with("/setnas") {
             // Inform user that settings can only be configured via environment variables
             bot.send_message(
@@ -760,96 +2241,6 @@ async fn message_handler(
             ).await?;
             return Ok(());
         }
-
-        if text.starts_with("/ssh") {
-            let parts: Vec<&str> = text.split_whitespace().collect();
-
-            let mut config = synology_config.lock().await;
-
-            // Ensure logged in
-            match config.ensure_logged_in().await {
-                Ok(true) => {
-                    // Now we're logged in, proceed with SSH operations
-                    if let Some(client) = &mut config.client {
-                        if parts.len() == 1 {
-                            // Just /ssh - get status
-                            match client.get_ssh_status().await {
-                                Ok(status) => {
-                                    let status_text = if status { "enabled" } else { "disabled" };
-                                    bot.send_message(
-                                        msg.chat.id,
-                                        format!("SSH service is currently {}", status_text)
-                                    ).await?;
-                                },
-                                Err(e) => {
-                                    bot.send_message(
-                                        msg.chat.id,
-                                        format!("Failed to get SSH status: {}", e)
-                                    ).await?;
-                                }
-                            }
-                        } else if parts.len() >= 2 {
-                            // /ssh on or /ssh off - set status
-                            let command = parts[1].to_lowercase();
-
-                            if command == "on" || command == "enable" {
-                                match client.toggle_ssh(true).await {
-                                    Ok(_) => {
-                                        info!("SSH service enabled by chat {}", msg.chat.id.0);
-                                        bot.send_message(
-                                            msg.chat.id,
-                                            "SSH service has been enabled"
-                                        ).await?;
-                                    },
-                                    Err(e) => {
-                                        error!("Failed to enable SSH service: {}", e);
-                                        bot.send_message(
-                                            msg.chat.id,
-                                            format!("Failed to enable SSH service: {}", e)
-                                        ).await?;
-                                    }
-                                }
-                            } else if command == "off" || command == "disable" {
-                                match client.toggle_ssh(false).await {
-                                    Ok(_) => {
-                                        info!("SSH service disabled by chat {}", msg.chat.id.0);
-                                        bot.send_message(
-                                            msg.chat.id,
-                                            "SSH service has been disabled"
-                                        ).await?;
-                                    },
-                                    Err(e) => {
-                                        error!("Failed to disable SSH service: {}", e);
-                                        bot.send_message(
-                                            msg.chat.id,
-                                            format!("Failed to disable SSH service: {}", e)
-                                        ).await?;
-                                    }
-                                }
-                            } else {
-                                bot.send_message(
-                                    msg.chat.id,
-                                    "Usage: /ssh [on|off] - Get SSH status or enable/disable SSH"
-                                ).await?;
-                            }
-                        }
-                    }
-                },
-                Ok(false) => {
-                    bot.send_message(
-                        msg.chat.id, 
-                        "Could not login to Synology NAS. Please check your SYNOLOGY_USERNAME and SYNOLOGY_PASSWORD environment variables."
-                    ).await?;
-                },
-                Err(e) => {
-                    bot.send_message(
-                        msg.chat.id, 
-                        format!("Failed to login to Synology NAS: {}", e)
-                    ).await?;
-                }
-            }
-            return Ok(());
-        }
     }
 
     Ok(())
@@ -861,8 +2252,12 @@ async fn main() {
     // This must happen before logger initialization so that STB_RUST_LOG from .env is respected.
     let dotenv_result = dotenvy::dotenv();
 
+    // Config file and environment; read before the logger so that STB_RUST_LOG may come from the file
+    let settings = Settings::load();
+
     // Initialize the logger
-    env_logger::Builder::from_env(env_logger::Env::new().filter_or("STB_RUST_LOG", "debug")).init();
+    let log_filter = settings.as_ref().ok().and_then(|s| s.get("STB_RUST_LOG")).unwrap_or_else(|| "debug".to_string());
+    env_logger::Builder::new().parse_filters(&log_filter).init();
 
     // Log whether .env was found and from which path, or that it was not found
     match &dotenv_result {
@@ -885,22 +2280,62 @@ async fn main() {
         Err(e) => warn!("Could not determine local IP address: {}", e),
     };
 
-    // Get the bot token from environment variable
-    let bot_token = std::env::var("STB_TELEGRAM_BOT_TOKEN")
-        .expect("STB_TELEGRAM_BOT_TOKEN environment variable is not set");
+    // Validate the whole configuration once; every problem is reported before the bot stops
+    let config = match settings.and_then(|settings| {
+        let config = Config::from_settings(&settings)?;
+        for key in settings.unused_keys() {
+            warn!("Config file: setting '{}' is unknown or not used", key);
+        }
+        Ok(config)
+    }) {
+        Ok(config) => Arc::new(config),
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    match &config.file {
+        Some(file) => info!("Configuration loaded from {} and the environment", file.display()),
+        None => info!("Configuration loaded from the environment"),
+    }
 
-    // Initialize Synology configuration
-    let synology_config = Arc::new(Mutex::new(SynologyConfig::new()));
+    // State that survives restarts (pending SSH auto-disable, DSM device token)
+    let state_store = Arc::new(Mutex::new(StateStore::in_dir(&config.state_dir)));
 
     // OpenBao unseal support (optional)
-    let bao_config: Arc<Option<BaoConfig>> = Arc::new(BaoConfig::from_env());
+    let bao_config: Arc<Option<BaoConfig>> = Arc::new(config.bao.as_ref().map(BaoConfig::new));
     if bao_config.is_none() {
-        info!("OpenBao unseal support disabled (STB_BAO_ADDR / STB_UNSEAL_WEB_URL not fully set)");
+        info!("OpenBao unseal support disabled (STB_BAO_ADDR / STB_UNSEAL_WEB_URL not set)");
     }
-    let pending_unseal: Arc<Mutex<Option<UnsealSession>>> = Arc::new(Mutex::new(None));
+
+    let unseal_sessions: Arc<Mutex<UnsealSessions>> = Arc::new(Mutex::new(UnsealSessions::default()));
+    let power_confirmations: Arc<Mutex<PowerConfirmations>> = Arc::new(Mutex::new(PowerConfirmations::default()));
 
     info!("Initializing bot ()...");
-    let bot = Bot::new(bot_token);
+    let bot = Bot::new(&config.telegram_bot_token);
+
+    // Initialize the Synology configuration of every NAS profile
+    let mut units = Vec::new();
+    for (idx, settings) in config.nas.iter().enumerate() {
+        let state_key = (idx > 0).then(|| settings.name.clone());
+        let mut synology = SynologyConfig::new(settings, bao_config.clone());
+        synology.device_id = state_store.lock().await.get().nas(state_key.as_deref()).synology_device_id;
+        // A sealed vault is fine here, the credentials are fetched again on first use
+        synology.reload_credentials().await;
+        let synology = Arc::new(Mutex::new(synology));
+
+        // Automatic SSH disable after a timed enable
+        let nas_ref = NasRef {
+            name: settings.name.clone(),
+            state_key: state_key.clone(),
+            prefix: if config.nas.len() > 1 { format!("[{}] ", settings.name) } else { String::new() },
+        };
+        let ssh_timer = Arc::new(SshAutoDisable::new(bot.clone(), nas_ref.clone(), synology.clone(), state_store.clone()));
+        ssh_timer.restore().await;
+
+        units.push(Nas { name: nas_ref.name, prefix: nas_ref.prefix, synology, ssh_timer, state_key, wol: settings.wol.clone() });
+    }
+    let nas_units = Arc::new(NasUnits::new(units));
 
     // Set the chat menu button to show commands
     info!("Setting chat menu button...");
@@ -916,10 +2351,13 @@ async fn main() {
         .await
         .expect("Failed to register commands");
 
+    // Allow-list of users and chats with their roles
+    let access_control = Arc::new(config.access.clone());
+
     // Create a message handler
     let default_handler = Update::filter_message().branch(
         dptree::entry()
-            .filter_command::<Command>()
+            .filter_map(|msg: Message, me: Me| parse_command(&msg, &me))
             .endpoint(answer_command)
     );
 
@@ -932,8 +2370,21 @@ async fn main() {
     // Create a handler for inline queries
     let inline_query_handler = Update::filter_inline_query().endpoint(inline_query_handler);
 
-    // Combine handlers
+    // Combine handlers behind the authorization gate: every update is checked here, not in the handlers
     let handler = dptree::entry()
+        .map_async(|update: Update, me: Me, acl: Arc<AccessControl>, sessions: Arc<Mutex<UnsealSessions>>| async move {
+            let share_pending = match update.user() {
+                Some(user) => sessions.lock().await.awaiting_share_from(user.id).is_some(),
+                None => false,
+            };
+            Authorization {
+                granted: acl.role_for_update(&update),
+                required: required_role(&update, &me),
+                share_pending,
+            }
+        })
+        .branch(Update::filter_message().filter(|auth: Authorization| auth.share_only()).endpoint(share_holder_handler))
+        .branch(dptree::filter(|auth: Authorization| !auth.is_allowed()).endpoint(unauthorized_handler))
         .branch(default_handler)
         .branch(message_handler)
         .branch(callback_handler)
@@ -944,10 +2395,65 @@ async fn main() {
     let me = bot.get_me().await.expect("Failed to get bot info");
     info!("Bot username: @{}", me.username());
 
+    // Log out of DSM once the session has been idle for a while
+    {
+        let nas_units = nas_units.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(60));
+            loop {
+                interval.tick().await;
+                for nas in &nas_units.units {
+                    let mut config = nas.synology.lock().await;
+                    let idle_timeout = config.session_idle_timeout;
+                    if let Some(client) = config.client.as_mut() {
+                        client.logout_if_idle(idle_timeout).await;
+                    }
+                }
+            }
+        });
+    }
+
+    // Background monitoring and OpenBao seal watchdog, alerting the allowed chats
+    monitor::spawn(
+        bot.clone(),
+        config.monitor.clone(),
+        nas_units.clone(),
+        bao_config.clone(),
+        access_control.clone()
+    );
+
+    // Keep the OpenBao token renewed (only with STB_BAO_TOKEN_FILE or AppRole)
+    {
+        let bao_config = bao_config.clone();
+        tokio::spawn(async move {
+            if let Some(bao) = bao_config.as_ref() {
+                bao.cluster.maintain_token().await;
+            }
+        });
+    }
+
+    // Drop expired unseal sessions and tell their chats
+    unseal::spawn_sweeper(bot.clone(), unseal_sessions.clone());
+
+    // Built-in unseal page (optional), receives the ciphertexts from the browser
+    if bao_config.is_some() && let Some(web_config) = config.unseal_web.clone() {
+        web::spawn(bot.clone(), web_config, bao_config.clone(), unseal_sessions.clone(), nas_units.clone()).await;
+    }
+
     Dispatcher::builder(bot, handler)
-        .dependencies(dptree::deps![synology_config, bao_config, pending_unseal])
+        .dependencies(dptree::deps![config, nas_units.clone(), bao_config, unseal_sessions, power_confirmations, access_control, state_store])
         .enable_ctrlc_handler()
         .build()
         .dispatch()
         .await;
+
+    // Shutdown: end the DSM sessions instead of leaving them to expire
+    for nas in &nas_units.units {
+        if let Some(client) = nas.synology.lock().await.client.as_mut()
+            && let Err(e) = client.logout().await
+        {
+            error!("Failed to logout from Synology NAS {} on shutdown: {}", nas.name, e);
+        }
+    }
+    info!("Bot stopped");
 }
@@ -0,0 +1,418 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use teloxide::prelude::*;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+use log::{debug, info, warn};
+
+use crate::auth::AccessControl;
+use crate::config::Settings;
+use crate::ssh_timer::{format_duration, parse_duration};
+use crate::synology::{StorageInfo, SynologyClientError};
+use crate::{BaoConfig, Nas, NasUnits, CALLBACK_UNSEAL};
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(5 * 60);
+const DEFAULT_BAO_INTERVAL: Duration = Duration::from_secs(30);
+const DEFAULT_VOLUME_USAGE_PERCENT: f64 = 90.0;
+const DEFAULT_DISK_TEMPERATURE: i32 = 55;
+
+/// Which checks the monitor runs and their thresholds. A threshold of `None` disables the check.
+#[derive(Debug, Clone)]
+pub struct MonitorConfig {
+    /// Poll interval of the NAS checks, `None` if they are switched off
+    pub interval: Option<Duration>,
+    /// Alert when a volume is fuller than this (percent)
+    pub volume_usage_percent: Option<f64>,
+    /// Alert when a disk is hotter than this (°C)
+    pub disk_temperature: Option<i32>,
+    /// Alert on disks whose SMART status is not normal
+    pub disk_smart: bool,
+    /// Alert on degraded or crashed storage pools and volumes
+    pub pool_status: bool,
+    /// Alert while SSH is enabled
+    pub ssh_enabled: bool,
+    /// Poll interval of the OpenBao seal watchdog, `None` if it is switched off
+    pub bao_interval: Option<Duration>,
+}
+
+impl MonitorConfig {
+    pub fn from_settings(settings: &Settings) -> Result<Self, Vec<String>> {
+        let mut errors = Vec::new();
+        let config = MonitorConfig {
+            interval: interval(settings, "STB_MONITOR_INTERVAL", DEFAULT_INTERVAL, &mut errors),
+            volume_usage_percent: threshold(settings, "STB_MONITOR_VOLUME_USAGE", DEFAULT_VOLUME_USAGE_PERCENT, &mut errors),
+            disk_temperature: threshold(settings, "STB_MONITOR_DISK_TEMP", DEFAULT_DISK_TEMPERATURE, &mut errors),
+            disk_smart: switch(settings, "STB_MONITOR_DISK_SMART", true),
+            pool_status: switch(settings, "STB_MONITOR_POOL_STATUS", true),
+            ssh_enabled: switch(settings, "STB_MONITOR_SSH", false),
+            bao_interval: interval(settings, "STB_MONITOR_BAO_INTERVAL", DEFAULT_BAO_INTERVAL, &mut errors),
+        };
+        if errors.is_empty() { Ok(config) } else { Err(errors) }
+    }
+}
+
+// Poll interval; "off" switches the loop off
+fn interval(settings: &Settings, var: &str, default: Duration, errors: &mut Vec<String>) -> Option<Duration> {
+    match settings.get(var) {
+        None => Some(default),
+        Some(v) if is_off(&v) => None,
+        Some(v) => Some(parse_duration(&v).unwrap_or_else(|| {
+            errors.push(format!("{} '{}' is not a valid duration (e.g. {}, or off)", var, v, format_duration(default)));
+            default
+        })),
+    }
+}
+
+fn is_off(value: &str) -> bool {
+    matches!(value.trim().to_lowercase().as_str(), "off" | "false" | "0" | "no")
+}
+
+// Alert threshold; "off" disables the check
+fn threshold<T: FromStr + Copy + std::fmt::Display>(settings: &Settings, var: &str, default: T, errors: &mut Vec<String>) -> Option<T> {
+    match settings.get(var) {
+        None => Some(default),
+        Some(v) if is_off(&v) => None,
+        Some(v) => Some(v.parse().unwrap_or_else(|_| {
+            errors.push(format!("{} '{}' is not a valid threshold (e.g. {}, or off)", var, v, default));
+            default
+        })),
+    }
+}
+
+fn switch(settings: &Settings, var: &str, default: bool) -> bool {
+    match settings.get(var) {
+        None => default,
+        Some(v) => !is_off(&v),
+    }
+}
+
+/// A condition that is currently out of bounds. `key` identifies it across polls.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alert {
+    pub key: String,
+    pub message: String,
+}
+
+impl Alert {
+    fn new(key: impl Into<String>, message: impl Into<String>) -> Self {
+        Alert { key: key.into(), message: message.into() }
+    }
+}
+
+/// Remembers which alerts have been sent, so each one is reported once and followed by a
+/// "recovered" message when it clears. Alerts are grouped by source (`storage@main`, `bao`, ...);
+/// a source that could not be polled keeps its alerts unchanged.
+#[derive(Debug, Default)]
+pub struct AlertTracker {
+    active: HashMap<String, String>,
+}
+
+impl AlertTracker {
+    /// Record the alerts currently firing for `source`. Returns the new alerts and the
+    /// messages of alerts that recovered.
+    pub fn update(&mut self, source: &str, firing: Vec<Alert>) -> (Vec<Alert>, Vec<String>) {
+        let prefix = format!("{}:", source);
+        let firing: HashMap<String, String> = firing
+            .into_iter()
+            .map(|a| (format!("{}{}", prefix, a.key), a.message))
+            .collect();
+
+        let recovered_keys: Vec<String> = self.active
+            .keys()
+            .filter(|k| k.starts_with(&prefix) && !firing.contains_key(*k))
+            .cloned()
+            .collect();
+        let recovered = recovered_keys
+            .iter()
+            .filter_map(|k| self.active.remove(k))
+            .collect();
+
+        let mut new = Vec::new();
+        for (key, message) in firing {
+            if !self.active.contains_key(&key) {
+                self.active.insert(key.clone(), message.clone());
+                new.push(Alert { key, message });
+            }
+        }
+        new.sort_by(|a, b| a.key.cmp(&b.key));
+
+        (new, recovered)
+    }
+}
+
+/// Storage conditions that cross the configured thresholds.
+pub fn evaluate_storage(info: &StorageInfo, config: &MonitorConfig) -> Vec<Alert> {
+    let mut alerts = Vec::new();
+
+    for volume in &info.volumes {
+        let name = if volume.vol_path.is_empty() { &volume.id } else { &volume.vol_path };
+        if let Some(threshold) = config.volume_usage_percent {
+            let used = volume.size.used_percent();
+            if used >= threshold {
+                alerts.push(Alert::new(
+                    format!("volume:{}:usage", volume.id),
+                    format!("Volume {} is {:.0}% full (threshold {:.0}%)", name, used, threshold)
+                ));
+            }
+        }
+        if config.pool_status && !volume.status.eq_ignore_ascii_case("normal") {
+            alerts.push(Alert::new(
+                format!("volume:{}:status", volume.id),
+                format!("Volume {} is {}", name, volume.status.to_uppercase())
+            ));
+        }
+    }
+
+    if config.pool_status {
+        for pool in info.storage_pools.iter().filter(|p| p.is_crashed() || p.is_degraded()) {
+            alerts.push(Alert::new(
+                format!("pool:{}", pool.id),
+                format!("Storage pool {} ({}) is {}", pool.num_id.unwrap_or_default(), pool.raid_label(), pool.status.to_uppercase())
+            ));
+        }
+    }
+
+    for disk in &info.disks {
+        let name = if disk.name.is_empty() { &disk.id } else { &disk.name };
+        if config.disk_smart && !disk.smart_ok() {
+            alerts.push(Alert::new(
+                format!("disk:{}:smart", disk.id),
+                format!("{} reports SMART status {}", name, disk.smart_status.as_deref().unwrap_or_default())
+            ));
+        }
+        if let (Some(threshold), Some(temp)) = (config.disk_temperature, disk.temp) && temp >= threshold {
+            alerts.push(Alert::new(
+                format!("disk:{}:temp", disk.id),
+                format!("{} is at {} °C (threshold {} °C)", name, temp, threshold)
+            ));
+        }
+    }
+
+    alerts
+}
+
+/// Start the monitor next to the dispatcher: the NAS checks and the OpenBao seal watchdog run
+/// on their own intervals and push alerts to every allow-listed chat.
+pub fn spawn(
+    bot: Bot,
+    config: MonitorConfig,
+    nas_units: Arc<NasUnits>,
+    bao_config: Arc<Option<BaoConfig>>,
+    access_control: Arc<AccessControl>
+) {
+    match config.interval {
+        Some(period) => {
+            info!("Monitoring enabled (interval {}): {:?}", format_duration(period), config);
+            let bot = bot.clone();
+            let config = config.clone();
+            let access_control = access_control.clone();
+            let nas_units = nas_units.clone();
+            tokio::spawn(async move {
+                let mut tracker = AlertTracker::default();
+                let mut interval = tokio::time::interval(period);
+                loop {
+                    interval.tick().await;
+                    for nas in &nas_units.units {
+                        poll_nas(&bot, &config, nas, &access_control, &mut tracker).await;
+                    }
+                }
+            });
+        },
+        None => info!("NAS monitoring disabled (STB_MONITOR_INTERVAL=off)"),
+    }
+
+    match (config.bao_interval, bao_config.is_some()) {
+        (Some(period), true) => {
+            info!("OpenBao seal watchdog enabled (interval {})", format_duration(period));
+            tokio::spawn(async move {
+                let mut tracker = AlertTracker::default();
+                let mut interval = tokio::time::interval(period);
+                let mut was_sealed = false;
+                loop {
+                    interval.tick().await;
+                    if let Some(bao) = bao_config.as_ref() {
+                        let sealed = watch_seal(&bot, bao, &access_control, &mut tracker).await;
+                        if was_sealed && !sealed {
+                            nas_units.reload_credentials().await;
+                        }
+                        was_sealed = sealed;
+                    }
+                }
+            });
+        },
+        (None, true) => info!("OpenBao seal watchdog disabled (STB_MONITOR_BAO_INTERVAL=off)"),
+        (_, false) => {}
+    }
+}
+
+// Alert sources are per NAS ("storage@backup"), the messages name the NAS if there are several
+async fn poll_nas(
+    bot: &Bot,
+    config: &MonitorConfig,
+    nas: &Nas,
+    access_control: &AccessControl,
+    tracker: &mut AlertTracker
+) {
+    debug!("Monitor poll of {}", nas.name);
+    let mut updates: Vec<(&str, Vec<Alert>)> = Vec::new();
+
+    {
+        let mut synology = nas.synology.lock().await;
+        match synology.ensure_logged_in(None).await {
+            Ok(true) => if let Some(client) = synology.client.as_mut() {
+                match client.get_storage_info().await {
+                    Ok(info) => {
+                        updates.push(("nas", vec![]));
+                        updates.push(("storage", evaluate_storage(&info, config)));
+                    },
+                    // Only a chat can answer the prompt, so point there instead of asking for the code
+                    Err(SynologyClientError::OtpRequired) => updates.push(("nas", vec![Alert::new(
+                        "unreachable",
+                        "Synology NAS cannot be polled: DSM asks for a 2-step verification code. Send a NAS command such as /storage and reply with the code in that chat"
+                    )])),
+                    Err(e) => updates.push(("nas", vec![Alert::new("unreachable", format!("Synology NAS cannot be polled: {}", e))])),
+                }
+                if config.ssh_enabled {
+                    match client.get_ssh_status().await {
+                        Ok(true) => updates.push(("ssh", vec![Alert::new("enabled", "SSH service is enabled")])),
+                        Ok(false) => updates.push(("ssh", vec![])),
+                        Err(e) => warn!("Monitor: failed to get SSH status of {}: {}", nas.name, e),
+                    }
+                }
+            },
+            Ok(false) => debug!("Monitor: Synology credentials of {} not set, skipping NAS checks", nas.name),
+            Err(e) => updates.push(("nas", vec![Alert::new("unreachable", format!("Synology NAS cannot be polled: {}", e))])),
+        }
+    }
+
+    for (source, firing) in updates {
+        let firing = firing
+            .into_iter()
+            .map(|alert| Alert { message: format!("{}{}", nas.prefix, alert.message), ..alert })
+            .collect();
+        report(bot, access_control, tracker, &format!("{}@{}", source, nas.name), firing, None).await;
+    }
+}
+
+// Seal watchdog: an unsealed -> sealed transition (or a vault that comes up sealed) is reported
+// once, with an "Unseal now" button that starts the same unseal session as /unseal
+// Returns whether any node is sealed
+async fn watch_seal(bot: &Bot, bao: &BaoConfig, access_control: &AccessControl, tracker: &mut AlertTracker) -> bool {
+    let mut firing = Vec::new();
+    for node in bao.cluster.nodes() {
+        match node.seal_status().await {
+            Ok(status) if status.initialized && status.sealed => firing.push(Alert::new(
+                format!("sealed:{}", node.addr()),
+                format!("OpenBao node {} is SEALED (progress {}/{})", node.addr(), status.progress, status.t)
+            )),
+            Ok(_) => {},
+            Err(e) => firing.push(Alert::new(
+                format!("unreachable:{}", node.addr()),
+                format!("OpenBao node {} cannot be reached: {}", node.addr(), e)
+            )),
+        }
+    }
+    let keyboard = InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("🔑 Unseal now", CALLBACK_UNSEAL),
+    ]]);
+    let sealed = firing.iter().any(|alert| alert.key.starts_with("sealed:"));
+    report(bot, access_control, tracker, "bao", firing, Some(keyboard)).await;
+    sealed
+}
+
+// Send new alerts (with an optional keyboard) and recovery messages for one source
+async fn report(
+    bot: &Bot,
+    access_control: &AccessControl,
+    tracker: &mut AlertTracker,
+    source: &str,
+    firing: Vec<Alert>,
+    keyboard: Option<InlineKeyboardMarkup>
+) {
+    let (new, recovered) = tracker.update(source, firing);
+    for alert in new {
+        info!("Monitor alert {}: {}", alert.key, alert.message);
+        // Buttons only make sense on the alert that needs an action
+        let markup = keyboard.clone().filter(|_| alert.key.starts_with(&format!("{}:sealed", source)));
+        notify(bot, access_control, &format!("🚨 {}", alert.message), markup).await;
+    }
+    for message in recovered {
+        info!("Monitor recovered: {}", message);
+        notify(bot, access_control, &format!("✅ Recovered: {}", message), None).await;
+    }
+}
+
+async fn notify(bot: &Bot, access_control: &AccessControl, text: &str, keyboard: Option<InlineKeyboardMarkup>) {
+    for chat_id in access_control.notification_chats() {
+        let request = bot.send_message(chat_id, text);
+        let result = match keyboard.clone() {
+            Some(keyboard) => request.reply_markup(keyboard).await,
+            None => request.await,
+        };
+        if let Err(e) = result {
+            warn!("Failed to send monitor message to chat {}: {}", chat_id.0, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> MonitorConfig {
+        MonitorConfig {
+            interval: Some(DEFAULT_INTERVAL),
+            volume_usage_percent: Some(90.0),
+            disk_temperature: Some(50),
+            disk_smart: true,
+            pool_status: true,
+            ssh_enabled: false,
+            bao_interval: Some(DEFAULT_BAO_INTERVAL),
+        }
+    }
+
+    #[test]
+    fn storage_thresholds() {
+        let info: StorageInfo = serde_json::from_value(serde_json::json!({
+            "volumes": [
+                { "id": "volume_1", "vol_path": "/volume1", "status": "normal", "size": { "total": "100", "used": "95" } },
+                { "id": "volume_2", "vol_path": "/volume2", "status": "normal", "size": { "total": "100", "used": "10" } }
+            ],
+            "storagePools": [{ "id": "reuse_1", "num_id": 1, "status": "crashed", "device_type": "raid_5" }],
+            "disks": [
+                { "id": "sata1", "name": "Drive 1", "status": "normal", "smart_status": "normal", "temp": 51 },
+                { "id": "sata2", "name": "Drive 2", "status": "normal", "smart_status": "normal", "temp": 30 }
+            ]
+        })).unwrap();
+
+        let keys: Vec<String> = evaluate_storage(&info, &config()).into_iter().map(|a| a.key).collect();
+        assert_eq!(keys, vec!["volume:volume_1:usage", "pool:reuse_1", "disk:sata1:temp"]);
+
+        let relaxed = MonitorConfig { volume_usage_percent: None, disk_temperature: None, ..config() };
+        let keys: Vec<String> = evaluate_storage(&info, &relaxed).into_iter().map(|a| a.key).collect();
+        assert_eq!(keys, vec!["pool:reuse_1"]);
+    }
+
+    #[test]
+    fn alerts_are_deduplicated_and_recover() {
+        let mut tracker = AlertTracker::default();
+        let sealed = || vec![Alert::new("sealed", "OpenBao is SEALED")];
+
+        let (new, recovered) = tracker.update("bao", sealed());
+        assert_eq!(new.len(), 1);
+        assert!(recovered.is_empty());
+
+        // Still firing: nothing to report
+        let (new, recovered) = tracker.update("bao", sealed());
+        assert!(new.is_empty() && recovered.is_empty());
+
+        // Other sources do not touch the bao alert
+        let (new, recovered) = tracker.update("storage", vec![]);
+        assert!(new.is_empty() && recovered.is_empty());
+
+        let (new, recovered) = tracker.update("bao", vec![]);
+        assert!(new.is_empty());
+        assert_eq!(recovered, vec!["OpenBao is SEALED".to_string()]);
+    }
+}
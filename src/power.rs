@@ -0,0 +1,220 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use teloxide::prelude::*;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup, MessageId};
+use tokio::sync::Mutex;
+use log::{info, warn};
+
+use crate::confirm::Confirmations;
+use crate::ssh_timer::format_duration;
+use crate::{Nas, SynologyConfig, CALLBACK_POWER_CANCEL, CALLBACK_POWER_CONFIRM};
+
+/// How long the confirmation buttons of /reboot and /shutdown are valid
+pub const POWER_CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
+/// How often DSM is polled while it reboots
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// A poll that takes longer counts as "DSM does not answer"
+const POLL_TIMEOUT: Duration = Duration::from_secs(5);
+/// DSM keeps answering for a while after accepting the reboot
+const GOING_DOWN_TIMEOUT: Duration = Duration::from_secs(3 * 60);
+/// Longest wait for DSM to come back
+const COMING_UP_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+/// Longest wait for DSM after a Wake-on-LAN packet, a cold start takes longer than a reboot
+const WAKE_TIMEOUT: Duration = Duration::from_secs(20 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerAction {
+    Reboot,
+    Shutdown,
+}
+
+impl PowerAction {
+    pub fn verb(self) -> &'static str {
+        match self {
+            PowerAction::Reboot => "reboot",
+            PowerAction::Shutdown => "shut down",
+        }
+    }
+}
+
+/// A /reboot or /shutdown waiting for its confirmation
+#[derive(Debug, Clone)]
+pub struct PendingPowerAction {
+    pub action: PowerAction,
+    /// Name of the NAS profile
+    pub nas: String,
+}
+
+pub type PowerConfirmations = Confirmations<PendingPowerAction>;
+
+pub fn create_power_confirm_menu(action: PowerAction, nonce: &str) -> InlineKeyboardMarkup {
+    let label = match action {
+        PowerAction::Reboot => "🔁 Reboot now",
+        PowerAction::Shutdown => "⏻ Shut down now",
+    };
+    InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback(label, format!("{}{}", CALLBACK_POWER_CONFIRM, nonce)),
+        InlineKeyboardButton::callback("Cancel", format!("{}{}", CALLBACK_POWER_CANCEL, nonce)),
+    ]])
+}
+
+/// What a reboot or shutdown would interrupt: File Station jobs, connected users and running
+/// Hyper Backup tasks. Parts DSM cannot tell are marked as unknown.
+pub async fn preflight_text(nas: &Nas, chat_id: ChatId) -> String {
+    let mut config = nas.synology.lock().await;
+    let client = match config.logged_in_client(chat_id).await {
+        Ok(client) => client,
+        Err(e) => return format!("❔ Pre-flight check not possible: {}", e),
+    };
+
+    let mut lines = Vec::new();
+    match client.get_background_tasks().await {
+        Ok(tasks) => {
+            let running: Vec<String> = tasks
+                .iter()
+                .filter(|t| !t.finished)
+                .map(|t| format!("{} ({:.0}%)", t.api.trim_start_matches("SYNO.FileStation."), t.progress * 100.0))
+                .collect();
+            lines.push(match running.len() {
+                0 => "✅ No running File Station tasks".to_string(),
+                n => format!("⚠️ {} running File Station task(s): {}", n, running.join(", ")),
+            });
+        },
+        Err(e) => lines.push(format!("❔ File Station tasks unknown: {}", e)),
+    }
+    match client.get_connections().await {
+        Ok(connections) => {
+            let who: Vec<String> = connections
+                .iter()
+                .map(|c| format!("{} via {} from {}", c.who, c.kind, c.from))
+                .collect();
+            lines.push(match who.len() {
+                0 => "✅ No active connections".to_string(),
+                n => format!("⚠️ {} active connection(s): {}", n, who.join("; ")),
+            });
+        },
+        Err(e) => lines.push(format!("❔ Connections unknown: {}", e)),
+    }
+    match client.get_backup_tasks().await {
+        Ok(backups) => {
+            let running: Vec<&str> = backups.iter().filter(|b| b.is_running()).map(|b| b.name.as_str()).collect();
+            lines.push(match running.len() {
+                0 => "✅ No running backups".to_string(),
+                n => format!("⚠️ {} running backup(s): {}", n, running.join(", ")),
+            });
+        },
+        Err(e) => lines.push(format!("❔ Backups unknown: {}", e)),
+    }
+    lines.join("\n")
+}
+
+/// One poll: DSM answers and accepts the login
+pub async fn dsm_answers(synology: &Mutex<SynologyConfig>, chat_id: ChatId) -> bool {
+    let check = async {
+        let mut config = synology.lock().await;
+        match config.logged_in_client(chat_id).await {
+            Ok(client) => client.get_system_info().await.is_ok(),
+            Err(_) => false,
+        }
+    };
+    tokio::time::timeout(POLL_TIMEOUT, check).await.unwrap_or(false)
+}
+
+/// Poll DSM until the login works, editing `message` with the time waited so far. Returns the
+/// time it took, or `None` after `timeout`.
+pub async fn wait_until_up(
+    bot: &Bot,
+    chat_id: ChatId,
+    message: MessageId,
+    synology: &Mutex<SynologyConfig>,
+    progress: &str,
+    timeout: Duration
+) -> Option<Duration> {
+    let started = Instant::now();
+    while started.elapsed() < timeout {
+        if dsm_answers(synology, chat_id).await {
+            return Some(started.elapsed());
+        }
+        let text = format!("{} ({} s)", progress, started.elapsed().as_secs());
+        if let Err(e) = bot.edit_message_text(chat_id, message, text).await {
+            warn!("Failed to update progress message: {}", e);
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+    None
+}
+
+/// Follow a reboot in the background: wait until DSM stops answering, then until it is back,
+/// and report the downtime in `message`.
+pub fn spawn_reboot_watch(bot: Bot, chat_id: ChatId, message: MessageId, synology: Arc<Mutex<SynologyConfig>>, prefix: String) {
+    tokio::spawn(async move {
+        let requested = Instant::now();
+        while dsm_answers(&synology, chat_id).await {
+            if requested.elapsed() > GOING_DOWN_TIMEOUT {
+                warn!("DSM still answers {} after the reboot request", format_duration(requested.elapsed()));
+                let text = format!("⚠️ {}DSM still answers {} after the reboot request. Did the reboot start?", prefix, format_duration(requested.elapsed()));
+                let _ = bot.edit_message_text(chat_id, message, text).await;
+                return;
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+
+        // The old session did not survive the reboot
+        synology.lock().await.client = None;
+        let down_since = Instant::now();
+        let progress = format!("🔁 {}NAS is rebooting, waiting for DSM...", prefix);
+        let text = match wait_until_up(&bot, chat_id, message, &synology, &progress, COMING_UP_TIMEOUT).await {
+            Some(downtime) => {
+                info!("DSM is back after a downtime of {} s", downtime.as_secs());
+                format!(
+                    "✅ {}NAS is back. Downtime {} s, {} s since the reboot request.",
+                    prefix,
+                    downtime.as_secs(),
+                    (down_since - requested + downtime).as_secs()
+                )
+            },
+            None => {
+                warn!("DSM did not come back within {}", format_duration(COMING_UP_TIMEOUT));
+                format!("❌ {}DSM did not answer within {} after the reboot. Please check the NAS.", prefix, format_duration(COMING_UP_TIMEOUT))
+            }
+        };
+        if let Err(e) = bot.edit_message_text(chat_id, message, text).await {
+            warn!("Failed to send reboot result: {}", e);
+        }
+    });
+}
+
+/// Wait in the background for DSM after a Wake-on-LAN packet and report in `message` when the
+/// NAS is up.
+pub fn spawn_wake_watch(bot: Bot, chat_id: ChatId, message: MessageId, synology: Arc<Mutex<SynologyConfig>>, prefix: String) {
+    tokio::spawn(async move {
+        let progress = format!("⏰ {}Magic packet sent, waiting for DSM...", prefix);
+        let text = match wait_until_up(&bot, chat_id, message, &synology, &progress, WAKE_TIMEOUT).await {
+            Some(waited) => {
+                info!("DSM is up {} s after the Wake-on-LAN packet", waited.as_secs());
+                format!("✅ {}NAS is up after {} s.", prefix, waited.as_secs())
+            },
+            None => {
+                warn!("DSM did not answer within {} after the Wake-on-LAN packet", format_duration(WAKE_TIMEOUT));
+                format!("❌ {}DSM did not answer within {} after the magic packet. Is Wake-on-LAN enabled in DSM (Hardware & Power)?", prefix, format_duration(WAKE_TIMEOUT))
+            }
+        };
+        if let Err(e) = bot.edit_message_text(chat_id, message, text).await {
+            warn!("Failed to send wake result: {}", e);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn confirmation_keeps_action_and_nas() {
+        let mut confirmations = PowerConfirmations::default();
+        let pending = PendingPowerAction { action: PowerAction::Shutdown, nas: "backup".to_string() };
+        let nonce = confirmations.create(pending, ChatId(1), POWER_CONFIRM_TIMEOUT);
+        let pending = confirmations.take(&nonce, ChatId(1)).unwrap();
+        assert_eq!((pending.action, pending.nas.as_str()), (PowerAction::Shutdown, "backup"));
+    }
+}
@@ -0,0 +1,91 @@
+use std::fmt;
+use std::path::Path;
+
+#[derive(Debug)]
+pub struct SecretError(String);
+
+impl fmt::Display for SecretError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for SecretError {}
+
+/// A secret setting, from the value of `<NAME>_FILE` (e.g. a Docker secret in `/run/secrets`) or
+/// from `<NAME>` itself. Setting both is an error, so it is always clear which one is used.
+pub fn resolve(name: &str, value: Option<String>, file: Option<String>) -> Result<Option<String>, SecretError> {
+    match (value, file) {
+        (Some(_), Some(_)) => Err(SecretError(format!("{} and {}_FILE are both set, use only one", name, name))),
+        (Some(value), None) => Ok(Some(value)),
+        (None, Some(file)) => read_secret_file(Path::new(file.trim()))
+            .map(Some)
+            .map_err(|e| SecretError(format!("{}_FILE: {}", name, e))),
+        (None, None) => Ok(None),
+    }
+}
+
+/// Read a secret from a file, without surrounding whitespace. Files that every user on the
+/// host can read are refused.
+pub fn read_secret_file(path: &Path) -> Result<String, SecretError> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let metadata = std::fs::metadata(path)
+            .map_err(|e| SecretError(format!("cannot read {}: {}", path.display(), e)))?;
+        if metadata.permissions().mode() & 0o004 != 0 {
+            return Err(SecretError(format!(
+                "{} is world-readable (mode {:o}), restrict it e.g. with chmod 600",
+                path.display(),
+                metadata.permissions().mode() & 0o777
+            )));
+        }
+    }
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| SecretError(format!("cannot read {}: {}", path.display(), e)))?;
+    let secret = content.trim();
+    if secret.is_empty() {
+        return Err(SecretError(format!("{} is empty", path.display())));
+    }
+    Ok(secret.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn secret_file(name: &str, content: &str, mode: u32) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("stb-secret-{}-{}", std::process::id(), name));
+        std::fs::write(&path, content).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+        path
+    }
+
+    #[test]
+    fn reads_trimmed_secret_file() {
+        let path = secret_file("ok", "  s3cr\\et\n", 0o600);
+        assert_eq!(read_secret_file(&path).unwrap(), "s3cr\\et");
+        assert_eq!(
+            resolve("STB_X", None, Some(path.display().to_string())).unwrap().as_deref(),
+            Some("s3cr\\et")
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn refuses_world_readable_file() {
+        let path = secret_file("open", "secret", 0o644);
+        let err = read_secret_file(&path).unwrap_err();
+        assert!(err.to_string().contains("world-readable"));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn value_and_file_are_exclusive() {
+        assert_eq!(resolve("STB_X", Some("v".into()), None).unwrap().as_deref(), Some("v"));
+        assert!(resolve("STB_X", Some("v".into()), Some("/run/secrets/x".into())).is_err());
+        assert!(resolve("STB_X", None, None).unwrap().is_none());
+    }
+}
@@ -0,0 +1,300 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use teloxide::prelude::*;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use log::{error, info, warn};
+
+use crate::state::{PendingSshDisable, StateStore};
+use crate::{nas_callback, SynologyConfig, CALLBACK_SSH_EXTEND, CALLBACK_SSH_OFF};
+
+/// How long before the automatic disable the chat gets a warning
+pub const SSH_AUTO_DISABLE_WARNING: Duration = Duration::from_secs(5 * 60);
+/// How much time the "Extend" button adds
+pub const SSH_AUTO_DISABLE_EXTENSION: Duration = Duration::from_secs(15 * 60);
+/// Longest accepted duration for `/ssh on <duration>`
+pub const SSH_AUTO_DISABLE_MAX: Duration = Duration::from_secs(24 * 60 * 60);
+/// Pause between attempts when the automatic disable fails
+pub const SSH_AUTO_DISABLE_RETRY: Duration = Duration::from_secs(60);
+
+/// Schedules the automatic SSH disable of one NAS after a timed enable. The deadline is kept
+/// in the state file, so `restore` can pick it up again after a restart.
+pub struct SshAutoDisable {
+    bot: Bot,
+    nas: NasRef,
+    synology_config: Arc<Mutex<SynologyConfig>>,
+    state: Arc<Mutex<StateStore>>,
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+/// Which NAS a timer belongs to
+#[derive(Clone)]
+pub struct NasRef {
+    /// Profile name, for the button data
+    pub name: String,
+    /// Key of the NAS in the state file, `None` for the first profile
+    pub state_key: Option<String>,
+    /// Put in front of messages, empty with a single NAS
+    pub prefix: String,
+}
+
+impl SshAutoDisable {
+    pub fn new(bot: Bot, nas: NasRef, synology_config: Arc<Mutex<SynologyConfig>>, state: Arc<Mutex<StateStore>>) -> Self {
+        SshAutoDisable {
+            bot,
+            nas,
+            synology_config,
+            state,
+            task: Mutex::new(None),
+        }
+    }
+
+    /// Re-arm a disable that was pending when the bot stopped.
+    pub async fn restore(&self) {
+        let pending = self.pending().await;
+        if let Some(pending) = pending {
+            info!("Restoring pending SSH auto-disable of {} at {} (chat {})", self.nas.name, pending.disable_at, pending.chat_id);
+            self.arm(pending).await;
+        }
+    }
+
+    async fn pending(&self) -> Option<PendingSshDisable> {
+        self.state.lock().await.get().nas(self.nas.state_key.as_deref()).ssh_auto_disable
+    }
+
+    async fn set_pending(&self, pending: Option<PendingSshDisable>) {
+        let key = self.nas.state_key.as_deref();
+        self.state.lock().await.update(|s| s.nas_mut(key).ssh_auto_disable = pending);
+    }
+
+    /// Disable SSH after `duration`; replaces any earlier schedule. Returns the deadline.
+    pub async fn schedule(&self, chat_id: ChatId, duration: Duration) -> u64 {
+        let pending = PendingSshDisable {
+            chat_id: chat_id.0,
+            disable_at: unix_now() + duration.as_secs(),
+        };
+        info!("SSH of {} will be disabled automatically in {} (chat {})", self.nas.name, format_duration(duration), chat_id.0);
+        self.set_pending(Some(pending)).await;
+        self.arm(pending).await;
+        pending.disable_at
+    }
+
+    /// Push the pending deadline back by `SSH_AUTO_DISABLE_EXTENSION`. Returns the new deadline,
+    /// or `None` if nothing is scheduled.
+    pub async fn extend(&self) -> Option<u64> {
+        let pending = self.pending().await?;
+        let extended = PendingSshDisable {
+            disable_at: pending.disable_at.max(unix_now()) + SSH_AUTO_DISABLE_EXTENSION.as_secs(),
+            ..pending
+        };
+        info!("SSH auto-disable of {} extended to {} (chat {})", self.nas.name, extended.disable_at, extended.chat_id);
+        self.set_pending(Some(extended)).await;
+        self.arm(extended).await;
+        Some(extended.disable_at)
+    }
+
+    /// Drop the pending disable, e.g. because SSH was switched off (or permanently on) by hand.
+    pub async fn cancel(&self) {
+        if let Some(task) = self.task.lock().await.take() {
+            task.abort();
+        }
+        if self.pending().await.is_some() {
+            info!("Pending SSH auto-disable of {} cancelled", self.nas.name);
+            self.set_pending(None).await;
+        }
+    }
+
+    /// Seconds left until the automatic disable, if one is pending. Zero once the deadline has
+    /// passed and the disable is still being retried.
+    pub async fn remaining(&self) -> Option<Duration> {
+        let pending = self.pending().await?;
+        Some(Duration::from_secs(pending.disable_at.saturating_sub(unix_now())))
+    }
+
+    async fn arm(&self, pending: PendingSshDisable) {
+        let bot = self.bot.clone();
+        let nas = self.nas.clone();
+        let synology_config = self.synology_config.clone();
+        let state = self.state.clone();
+        let task = tokio::spawn(run(bot, nas, synology_config, state, pending));
+        if let Some(previous) = self.task.lock().await.replace(task) {
+            previous.abort();
+        }
+    }
+}
+
+// Background task: warn shortly before the deadline, then switch SSH off
+async fn run(
+    bot: Bot,
+    nas: NasRef,
+    synology_config: Arc<Mutex<SynologyConfig>>,
+    state: Arc<Mutex<StateStore>>,
+    pending: PendingSshDisable
+) {
+    let chat_id = ChatId(pending.chat_id);
+    let remaining = pending.disable_at.saturating_sub(unix_now());
+
+    if remaining > SSH_AUTO_DISABLE_WARNING.as_secs() {
+        tokio::time::sleep(Duration::from_secs(remaining - SSH_AUTO_DISABLE_WARNING.as_secs())).await;
+        let keyboard = InlineKeyboardMarkup::new(vec![vec![
+            InlineKeyboardButton::callback(
+                format!("⏱️ Extend {}", format_duration(SSH_AUTO_DISABLE_EXTENSION)),
+                nas_callback(CALLBACK_SSH_EXTEND, &nas.name)
+            ),
+            InlineKeyboardButton::callback("❌ Disable now", nas_callback(CALLBACK_SSH_OFF, &nas.name)),
+        ]]);
+        if let Err(e) = bot.send_message(
+            chat_id,
+            format!("⏰ {}SSH will be disabled automatically in {}.", nas.prefix, format_duration(SSH_AUTO_DISABLE_WARNING))
+        )
+        .reply_markup(keyboard)
+        .await {
+            warn!("Failed to send SSH auto-disable warning: {}", e);
+        }
+    }
+
+    tokio::time::sleep(Duration::from_secs(pending.disable_at.saturating_sub(unix_now()))).await;
+
+    let command = if nas.prefix.is_empty() { "/ssh off".to_string() } else { format!("/ssh {} off", nas.name) };
+    disable_with_retry(
+        || {
+            let synology_config = synology_config.clone();
+            async move {
+                let mut config = synology_config.lock().await;
+                match config.logged_in_client(chat_id).await {
+                    Ok(client) => client.toggle_ssh(false).await.map_err(|e| e.to_string()),
+                    Err(e) => Err(e),
+                }
+            }
+        },
+        SSH_AUTO_DISABLE_RETRY,
+        |failures, e| {
+            error!("Automatic SSH disable of {} failed (attempt {}): {}", nas.name, failures, e);
+            // Tell the chat once; cancel() or a new schedule stops the retries
+            let notice = (failures == 1).then(|| (bot.clone(), format!(
+                "⚠️ {}Automatic SSH disable failed: {}. Retrying every {}, or run {}.",
+                nas.prefix, e, format_duration(SSH_AUTO_DISABLE_RETRY), command
+            )));
+            async move {
+                if let Some((bot, text)) = notice
+                    && let Err(e) = bot.send_message(chat_id, text).await {
+                    warn!("Failed to send SSH auto-disable notice: {}", e);
+                }
+            }
+        }
+    ).await;
+
+    info!("SSH service of {} disabled automatically (chat {})", nas.name, pending.chat_id);
+    // Only forget the schedule if it has not been replaced in the meantime
+    {
+        let key = nas.state_key.as_deref();
+        let mut state = state.lock().await;
+        if state.get().nas(key).ssh_auto_disable == Some(pending) {
+            state.update(|s| s.nas_mut(key).ssh_auto_disable = None);
+        }
+    }
+    if let Err(e) = bot.send_message(chat_id, format!("🔒 {}SSH service has been disabled automatically.", nas.prefix)).await {
+        warn!("Failed to send SSH auto-disable notice: {}", e);
+    }
+}
+
+// Call `disable` until it succeeds, waiting `retry` after each failure. `failed` gets the
+// number of failures so far and the error.
+async fn disable_with_retry<D, F>(
+    mut disable: impl FnMut() -> D,
+    retry: Duration,
+    mut failed: impl FnMut(u32, String) -> F
+) where
+    D: Future<Output = Result<(), String>>,
+    F: Future<Output = ()>,
+{
+    let mut failures = 0;
+    while let Err(e) = disable().await {
+        failures += 1;
+        failed(failures, e).await;
+        tokio::time::sleep(retry).await;
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Parses durations like `30m`, `1h`, `90s` or a plain number of minutes.
+pub fn parse_duration(input: &str) -> Option<Duration> {
+    let input = input.trim().to_lowercase();
+    let (number, unit) = match input.find(|c: char| !c.is_ascii_digit()) {
+        Some(idx) => input.split_at(idx),
+        None => (input.as_str(), "m"),
+    };
+    let value: u64 = number.parse().ok()?;
+    let seconds = match unit {
+        "s" | "sec" => value,
+        "m" | "min" => value.checked_mul(60)?,
+        "h" => value.checked_mul(3600)?,
+        _ => return None,
+    };
+    let duration = Duration::from_secs(seconds);
+    (!duration.is_zero() && duration <= SSH_AUTO_DISABLE_MAX).then_some(duration)
+}
+
+/// Human readable duration, e.g. `1 h 30 min` or `45 s`.
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match (secs / 3600, (secs % 3600) / 60) {
+        (0, 0) => format!("{} s", secs),
+        (0, m) => format!("{} min", m),
+        (h, 0) => format!("{} h", h),
+        (h, m) => format!("{} h {} min", h, m),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn failed_disable_is_retried_until_it_succeeds() {
+        let mut attempts = 0;
+        let mut failures = Vec::new();
+        disable_with_retry(
+            || {
+                attempts += 1;
+                let result = if attempts < 3 { Err("NAS unreachable".to_string()) } else { Ok(()) };
+                async move { result }
+            },
+            Duration::from_millis(1),
+            |count, e| {
+                failures.push((count, e));
+                async {}
+            }
+        ).await;
+        assert_eq!(attempts, 3);
+        assert_eq!(failures, vec![(1, "NAS unreachable".to_string()), (2, "NAS unreachable".to_string())]);
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("30m"), Some(Duration::from_secs(1800)));
+        assert_eq!(parse_duration("30"), Some(Duration::from_secs(1800)));
+        assert_eq!(parse_duration("2h"), Some(Duration::from_secs(7200)));
+        assert_eq!(parse_duration("90s"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("15min"), Some(Duration::from_secs(900)));
+        assert_eq!(parse_duration("0m"), None);
+        assert_eq!(parse_duration("25h"), None);
+        assert_eq!(parse_duration("m"), None);
+        assert_eq!(parse_duration("10d"), None);
+    }
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(format_duration(Duration::from_secs(45)), "45 s");
+        assert_eq!(format_duration(Duration::from_secs(900)), "15 min");
+        assert_eq!(format_duration(Duration::from_secs(3600)), "1 h");
+        assert_eq!(format_duration(Duration::from_secs(5400)), "1 h 30 min");
+    }
+}
@@ -0,0 +1,150 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use log::{debug, error, info, warn};
+
+/// Everything the bot has to remember across restarts.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PersistentState {
+    /// State of the first NAS profile, at the top level as in state files from before profiles
+    #[serde(flatten)]
+    pub primary: NasState,
+    /// State of the other NAS profiles, by name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub nas: BTreeMap<String, NasState>,
+}
+
+impl PersistentState {
+    /// State of a NAS profile; `None` is the first profile.
+    pub fn nas(&self, name: Option<&str>) -> NasState {
+        match name {
+            None => self.primary.clone(),
+            Some(name) => self.nas.get(name).cloned().unwrap_or_default(),
+        }
+    }
+
+    pub fn nas_mut(&mut self, name: Option<&str>) -> &mut NasState {
+        match name {
+            None => &mut self.primary,
+            Some(name) => self.nas.entry(name.to_string()).or_default(),
+        }
+    }
+}
+
+/// What is remembered per NAS
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NasState {
+    /// Pending automatic SSH disable, see `ssh_timer`
+    #[serde(default)]
+    pub ssh_auto_disable: Option<PendingSshDisable>,
+    /// DSM device token from the last 2-step verification, lets logins skip the code
+    #[serde(default)]
+    pub synology_device_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingSshDisable {
+    /// Chat that enabled SSH and receives the warning and the final notice
+    pub chat_id: i64,
+    /// Unix timestamp (seconds) at which SSH is disabled again
+    pub disable_at: u64,
+}
+
+/// Small JSON file store for `PersistentState` (`<state dir>/state.json`).
+pub struct StateStore {
+    path: PathBuf,
+    state: PersistentState,
+}
+
+impl StateStore {
+    pub fn in_dir(dir: &Path) -> Self {
+        Self::load(dir.join("state.json"))
+    }
+
+    fn load(path: PathBuf) -> Self {
+        let state = match std::fs::read_to_string(&path) {
+            Ok(content) => match serde_json::from_str(&content) {
+                Ok(state) => {
+                    info!("Loaded bot state from {}", path.display());
+                    state
+                },
+                Err(e) => {
+                    warn!("Ignoring unreadable state file {}: {}", path.display(), e);
+                    PersistentState::default()
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                debug!("No state file at {}; starting with empty state", path.display());
+                PersistentState::default()
+            },
+            Err(e) => {
+                warn!("Failed to read state file {}: {}", path.display(), e);
+                PersistentState::default()
+            }
+        };
+        StateStore { path, state }
+    }
+
+    pub fn get(&self) -> &PersistentState {
+        &self.state
+    }
+
+    /// Apply a change and write the state file. Write errors are logged, the in-memory state is kept.
+    pub fn update<F: FnOnce(&mut PersistentState)>(&mut self, change: F) {
+        change(&mut self.state);
+        if let Err(e) = self.save() {
+            error!("Failed to write state file {}: {}", self.path.display(), e);
+        }
+    }
+
+    fn save(&self) -> std::io::Result<()> {
+        if let Some(dir) = self.path.parent() && !dir.as_os_str().is_empty() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_string_pretty(&self.state)?;
+        // Write to a temporary file first so a crash never leaves a truncated state file behind
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, json)?;
+        // The state holds the DSM device token: keep it private to the bot's user
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(0o600))?;
+        }
+        std::fs::rename(&tmp, &self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_survives_reload() {
+        let dir = std::env::temp_dir().join(format!("stb-state-{}", crate::bao::random_session_id()));
+        let path = dir.join("state.json");
+
+        let mut store = StateStore::load(path.clone());
+        assert!(store.get().primary.ssh_auto_disable.is_none());
+        store.update(|s| s.primary.ssh_auto_disable = Some(PendingSshDisable { chat_id: -42, disable_at: 1_700_000_000 }));
+        store.update(|s| s.nas_mut(Some("backup")).synology_device_id = Some("dev".to_string()));
+
+        let reloaded = StateStore::load(path);
+        assert_eq!(
+            reloaded.get().primary.ssh_auto_disable,
+            Some(PendingSshDisable { chat_id: -42, disable_at: 1_700_000_000 })
+        );
+        assert_eq!(reloaded.get().nas(Some("backup")).synology_device_id.as_deref(), Some("dev"));
+        assert!(reloaded.get().nas(Some("office")).synology_device_id.is_none());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn reads_state_file_without_profiles() {
+        let json = r#"{"ssh_auto_disable":{"chat_id":7,"disable_at":100},"synology_device_id":"abc"}"#;
+        let state: PersistentState = serde_json::from_str(json).unwrap();
+        assert_eq!(state.nas(None).ssh_auto_disable, Some(PendingSshDisable { chat_id: 7, disable_at: 100 }));
+        assert_eq!(state.nas(None).synology_device_id.as_deref(), Some("abc"));
+        assert_eq!(serde_json::to_string(&state).unwrap(), json);
+    }
+}
@@ -1,13 +1,32 @@
 use reqwest::{Client, ClientBuilder, Error as ReqwestError};
 use serde::{Deserialize, Serialize};
-use log::{info, error, debug};
+use log::{info, warn, error, debug};
 use std::fmt;
 use std::error::Error;
+use std::collections::HashMap;
 use std::net::{IpAddr, Ipv4Addr};
+use std::time::{Duration, Instant};
 
-// Synology API endpoints
-const AUTH_ENDPOINT: &str = "/entry.cgi";
-const TERMINAL_ENDPOINT: &str = "/entry.cgi";
+use crate::tls::TlsConfig;
+
+// SYNO.API.Info always lives here; every other API path is discovered through it
+const INFO_PATH: &str = "query.cgi";
+
+// Versions of SYNO.API.Auth this client can talk
+const AUTH_MIN_VERSION: u32 = 3;
+const AUTH_MAX_VERSION: u32 = 6;
+
+// Session timeout (106), duplicated login (107) and invalid session (119): log in again and retry
+const SESSION_ERROR_CODES: [i32; 3] = [106, 107, 119];
+
+/// Default time without requests after which the session is logged out
+pub const DEFAULT_SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+// Device name registered with DSM when a device token is requested
+const DEVICE_NAME: &str = "synology-telegram-bot";
+
+/// How long a code requested after a 2-step verification failure is accepted
+pub const OTP_PROMPT_TIMEOUT: Duration = Duration::from_secs(300);
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SynologyResponse<T> {
@@ -47,6 +66,17 @@ impl SynologyError {
             118 => "The network connection is unstable or the system is busy.",
             119 => "Invalid session.",
             150 => "Request source IP does not match the login IP.",
+            // SYNO.API.Auth specific codes
+            400 => "No such account or incorrect password.",
+            401 => "Account disabled.",
+            402 => "Permission denied.",
+            403 => "2-step verification code required.",
+            404 => "Failed to authenticate 2-step verification code.",
+            406 => "Enforce to authenticate with 2-factor authentication code.",
+            407 => "Blocked IP source.",
+            408 => "Expired password cannot change.",
+            409 => "Expired password.",
+            410 => "Password must be changed.",
             _ => {
                 if self.code >= 120 && self.code <= 149 {
                     "Preserve for other purpose."
@@ -68,6 +98,12 @@ pub enum SynologyClientError {
     Generic(String),
     /// Login failed
     LoginFailed,
+    /// The NAS does not offer the API, or none of the versions this client supports
+    UnsupportedApi { api: String, reason: String },
+    /// The account uses 2-step verification and no valid device token is stored
+    OtpRequired,
+    /// DSM enforces 2-step verification, but the account has not set it up yet
+    OtpNotSetUp,
 }
 
 impl fmt::Display for SynologyClientError {
@@ -77,6 +113,9 @@ impl fmt::Display for SynologyClientError {
             SynologyClientError::Synology(err) => write!(f, "Synology API error: {} - {}", err.code, err.get_error_description()),
             SynologyClientError::Generic(msg) => write!(f, "{}", msg),
             SynologyClientError::LoginFailed => write!(f, "Login failed"),
+            SynologyClientError::UnsupportedApi { api, reason } => write!(f, "{} is not supported by this NAS: {}", api, reason),
+            SynologyClientError::OtpRequired => write!(f, "DSM asks for a 2-step verification code. Please reply with the current 6-digit code from your authenticator app within {} minutes", OTP_PROMPT_TIMEOUT.as_secs() / 60),
+            SynologyClientError::OtpNotSetUp => write!(f, "DSM enforces 2-step verification for this account, but it is not set up yet. Sign in to DSM once with this account, set up 2-step verification and try again"),
         }
     }
 }
@@ -95,13 +134,38 @@ impl From<SynologyError> for SynologyClientError {
     }
 }
 
+/// Entry of the SYNO.API.Info response: where an API lives and which versions it speaks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiInfo {
+    pub path: String,
+    #[serde(rename = "minVersion")]
+    pub min_version: u32,
+    #[serde(rename = "maxVersion")]
+    pub max_version: u32,
+    #[serde(rename = "requestFormat", default)]
+    pub request_format: Option<String>,
+}
+
+impl ApiInfo {
+    /// Highest version supported by both the NAS and the client, if the ranges overlap.
+    pub fn best_version(&self, client_min: u32, client_max: u32) -> Option<u32> {
+        let version = self.max_version.min(client_max);
+        (version >= self.min_version.max(client_min)).then_some(version)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AuthData {
     pub sid: String,
+    /// Device token, returned when logging in with `enable_device_token=yes`
+    #[serde(default)]
+    pub did: Option<String>,
 }
 
+/// Response of SYNO.FileStation.List `list` (files) and `list_share` (shares).
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FileListData {
+    #[serde(alias = "shares")]
     pub files: Vec<FileInfo>,
     pub total: i32,
     pub offset: i32,
@@ -118,6 +182,13 @@ pub struct FileInfo {
     pub name: String,
     pub path: String,
     pub isdir: bool,
+    /// Size and times, as requested with `additional`
+    #[serde(default)]
+    pub additional: FileAdditional,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct FileAdditional {
     pub size: Option<u64>,
     pub time: Option<FileTime>,
 }
@@ -129,24 +200,501 @@ pub struct FileTime {
     pub atime: u64,
 }
 
+/// A DSM file or network service that can be switched on and off through the Web API.
+#[derive(Debug)]
+pub struct ServiceDef {
+    /// Short name used in commands and callbacks, e.g. `smb`
+    pub name: &'static str,
+    /// Alternative names accepted in commands
+    pub aliases: &'static [&'static str],
+    /// Name shown to the user
+    pub label: &'static str,
+    pub api: &'static str,
+    /// API versions whose get/set parameters match this definition
+    pub min_version: u32,
+    pub max_version: u32,
+    pub get_method: &'static str,
+    pub set_method: &'static str,
+    /// Parameter of the set method that switches the service
+    pub enable_param: &'static str,
+    /// Fields of the get response that hold the on/off state; the first one present wins
+    pub status_fields: &'static [&'static str],
+}
+
+impl ServiceDef {
+    /// Map the `data` object of the service's get response to its on/off state.
+    pub fn status_from(&self, data: &serde_json::Value) -> Option<bool> {
+        self.status_fields
+            .iter()
+            .find_map(|field| data.get(field).and_then(json_flag))
+    }
+}
+
+// DSM reports flags as booleans, as "true"/"yes" strings or as numbers depending on the API
+fn json_flag(value: &serde_json::Value) -> Option<bool> {
+    match value {
+        serde_json::Value::Bool(b) => Some(*b),
+        serde_json::Value::Number(n) => n.as_i64().map(|n| n != 0),
+        serde_json::Value::String(s) => match s.to_lowercase().as_str() {
+            "true" | "yes" | "on" | "1" => Some(true),
+            "false" | "no" | "off" | "0" => Some(false),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+pub const SSH_SERVICE: ServiceDef = ServiceDef {
+    name: "ssh",
+    aliases: &[],
+    label: "SSH",
+    api: "SYNO.Core.Terminal",
+    min_version: 1,
+    max_version: 3,
+    get_method: "get",
+    set_method: "set",
+    enable_param: "enable_ssh",
+    status_fields: &["enable_ssh", "service_status", "ssh_status"],
+};
+
+/// All services known to the bot, in the order they are shown.
+pub const SERVICES: &[ServiceDef] = &[
+    SSH_SERVICE,
+    ServiceDef {
+        name: "telnet",
+        aliases: &[],
+        label: "Telnet",
+        api: "SYNO.Core.Terminal",
+        min_version: 1,
+        max_version: 3,
+        get_method: "get",
+        set_method: "set",
+        enable_param: "enable_telnet",
+        status_fields: &["enable_telnet"],
+    },
+    ServiceDef {
+        name: "smb",
+        aliases: &["samba", "cifs"],
+        label: "SMB",
+        api: "SYNO.Core.FileServ.SMB",
+        min_version: 1,
+        max_version: 3,
+        get_method: "get",
+        set_method: "set",
+        enable_param: "enable_samba",
+        status_fields: &["enable_samba"],
+    },
+    ServiceDef {
+        name: "nfs",
+        aliases: &[],
+        label: "NFS",
+        api: "SYNO.Core.FileServ.NFS",
+        min_version: 1,
+        max_version: 2,
+        get_method: "get",
+        set_method: "set",
+        enable_param: "enable_nfs",
+        status_fields: &["enable_nfs"],
+    },
+    ServiceDef {
+        name: "afp",
+        aliases: &[],
+        label: "AFP",
+        api: "SYNO.Core.FileServ.AFP",
+        min_version: 1,
+        max_version: 1,
+        get_method: "get",
+        set_method: "set",
+        enable_param: "enable_afp",
+        status_fields: &["enable_afp"],
+    },
+    ServiceDef {
+        name: "ftp",
+        aliases: &[],
+        label: "FTP",
+        api: "SYNO.Core.FileServ.FTP",
+        min_version: 1,
+        max_version: 3,
+        get_method: "get",
+        set_method: "set",
+        enable_param: "enable_ftp",
+        status_fields: &["enable_ftp"],
+    },
+    ServiceDef {
+        name: "rsync",
+        aliases: &[],
+        label: "rsync",
+        api: "SYNO.Backup.Service.NetworkBackup",
+        min_version: 1,
+        max_version: 1,
+        get_method: "get",
+        set_method: "set",
+        enable_param: "enable",
+        status_fields: &["enable", "enable_rsync"],
+    },
+    ServiceDef {
+        name: "snmp",
+        aliases: &[],
+        label: "SNMP",
+        api: "SYNO.Core.SNMP",
+        min_version: 1,
+        max_version: 1,
+        get_method: "get",
+        set_method: "set",
+        enable_param: "enable_snmp",
+        status_fields: &["enable_snmp"],
+    },
+];
+
+/// Look up a service by its name or one of its aliases (case-insensitive).
+pub fn find_service(name: &str) -> Option<&'static ServiceDef> {
+    let name = name.trim().to_lowercase();
+    SERVICES
+        .iter()
+        .find(|s| s.name == name || s.aliases.contains(&name.as_str()))
+}
+
+// DSM sends sizes as strings ("4000787030016") or numbers depending on the API version
+fn de_lenient_u64<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::Number(n) => n.as_u64().ok_or_else(|| serde::de::Error::custom("expected an unsigned number")),
+        serde_json::Value::String(s) if s.is_empty() => Ok(0),
+        serde_json::Value::String(s) => s.parse().map_err(serde::de::Error::custom),
+        serde_json::Value::Null => Ok(0),
+        other => Err(serde::de::Error::custom(format!("expected a size, got {}", other))),
+    }
+}
+
+/// Response of SYNO.Storage.CGI.Storage `load_info`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StorageInfo {
+    #[serde(default)]
+    pub volumes: Vec<Volume>,
+    #[serde(rename = "storagePools", default)]
+    pub storage_pools: Vec<StoragePool>,
+    #[serde(default)]
+    pub disks: Vec<Disk>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SpaceSize {
+    #[serde(deserialize_with = "de_lenient_u64", default)]
+    pub total: u64,
+    #[serde(deserialize_with = "de_lenient_u64", default)]
+    pub used: u64,
+}
+
+impl SpaceSize {
+    pub fn used_percent(&self) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.used as f64 * 100.0 / self.total as f64
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Volume {
+    pub id: String,
+    /// Mount path, e.g. `/volume1`
+    #[serde(default)]
+    pub vol_path: String,
+    pub status: String,
+    #[serde(default)]
+    pub size: SpaceSize,
+    #[serde(default)]
+    pub fs_type: Option<String>,
+    /// ID of the storage pool the volume lives on
+    #[serde(default)]
+    pub pool_path: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StoragePool {
+    pub id: String,
+    #[serde(default)]
+    pub num_id: Option<u32>,
+    pub status: String,
+    /// RAID type, e.g. `shr_without_disk_protect`, `raid_5`
+    #[serde(default, alias = "raidType")]
+    pub device_type: String,
+    #[serde(default)]
+    pub size: SpaceSize,
+    /// IDs of the member disks
+    #[serde(default)]
+    pub disks: Vec<String>,
+}
+
+impl StoragePool {
+    pub fn is_crashed(&self) -> bool {
+        self.status.eq_ignore_ascii_case("crashed")
+    }
+
+    pub fn is_degraded(&self) -> bool {
+        matches!(self.status.to_lowercase().as_str(), "degraded" | "attention" | "repairing" | "warning")
+    }
+
+    /// Human readable RAID type, e.g. `SHR-1` or `RAID 5`
+    pub fn raid_label(&self) -> String {
+        match self.device_type.to_lowercase().as_str() {
+            "shr_without_disk_protect" => "SHR (no protection)".to_string(),
+            "shr" | "shr_with_1_disk_protect" => "SHR-1".to_string(),
+            "shr_with_2_disk_protect" | "shr2" => "SHR-2".to_string(),
+            "basic" => "Basic".to_string(),
+            other if other.starts_with("raid_") => format!("RAID {}", &other[5..]),
+            other => other.to_uppercase(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Disk {
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub model: String,
+    pub status: String,
+    #[serde(default)]
+    pub smart_status: Option<String>,
+    /// Temperature in °C
+    #[serde(default)]
+    pub temp: Option<i32>,
+    #[serde(deserialize_with = "de_lenient_u64", default)]
+    pub size_total: u64,
+}
+
+impl Disk {
+    pub fn smart_ok(&self) -> bool {
+        self.smart_status
+            .as_deref()
+            .is_none_or(|s| s.eq_ignore_ascii_case("normal") || s.eq_ignore_ascii_case("safe"))
+    }
+}
+
+/// Response of SYNO.Core.System `info`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SystemInfo {
+    #[serde(default)]
+    pub model: String,
+    /// DSM version, e.g. `DSM 7.2-64570 Update 1`
+    #[serde(default)]
+    pub firmware_ver: String,
+    #[serde(default)]
+    pub serial: String,
+    /// Uptime as `hours:minutes:seconds`, e.g. `66:59:37`
+    #[serde(default)]
+    pub up_time: String,
+    /// System temperature in °C
+    #[serde(default)]
+    pub sys_temp: Option<i32>,
+    #[serde(default)]
+    pub temperature_warning: bool,
+    /// Local time of the NAS, e.g. `2024-05-01 10:04:09`
+    #[serde(default)]
+    pub time: String,
+}
+
+impl SystemInfo {
+    pub fn uptime(&self) -> Option<Duration> {
+        let parts: Vec<u64> = self.up_time.split(':').map(|p| p.trim().parse().ok()).collect::<Option<_>>()?;
+        match parts[..] {
+            [hours, minutes, seconds] => Some(Duration::from_secs(hours * 3600 + minutes * 60 + seconds)),
+            _ => None,
+        }
+    }
+}
+
+/// Response of SYNO.Core.System.Utilization `get`. Memory is in KB, throughput in bytes per second.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SystemUtilization {
+    #[serde(default)]
+    pub cpu: CpuLoad,
+    #[serde(default)]
+    pub memory: MemoryUsage,
+    #[serde(default)]
+    pub network: Vec<NetworkRate>,
+    #[serde(default)]
+    pub disk: DiskRates,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CpuLoad {
+    /// Load averages times 100
+    #[serde(rename = "1min_load", deserialize_with = "de_lenient_u64", default)]
+    pub load_1min: u64,
+    #[serde(rename = "5min_load", deserialize_with = "de_lenient_u64", default)]
+    pub load_5min: u64,
+    #[serde(rename = "15min_load", deserialize_with = "de_lenient_u64", default)]
+    pub load_15min: u64,
+    /// CPU usage in percent, split by kind
+    #[serde(deserialize_with = "de_lenient_u64", default)]
+    pub user_load: u64,
+    #[serde(deserialize_with = "de_lenient_u64", default)]
+    pub system_load: u64,
+    #[serde(deserialize_with = "de_lenient_u64", default)]
+    pub other_load: u64,
+}
+
+impl CpuLoad {
+    pub fn percent(&self) -> u64 {
+        (self.user_load + self.system_load + self.other_load).min(100)
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct MemoryUsage {
+    /// Used memory without buffers and cache, in percent
+    #[serde(deserialize_with = "de_lenient_u64", default)]
+    pub real_usage: u64,
+    #[serde(deserialize_with = "de_lenient_u64", default)]
+    pub memory_size: u64,
+    #[serde(deserialize_with = "de_lenient_u64", default)]
+    pub total_real: u64,
+    #[serde(deserialize_with = "de_lenient_u64", default)]
+    pub avail_real: u64,
+    #[serde(deserialize_with = "de_lenient_u64", default)]
+    pub buffer: u64,
+    #[serde(deserialize_with = "de_lenient_u64", default)]
+    pub cached: u64,
+    #[serde(deserialize_with = "de_lenient_u64", default)]
+    pub swap_usage: u64,
+    #[serde(deserialize_with = "de_lenient_u64", default)]
+    pub total_swap: u64,
+    #[serde(deserialize_with = "de_lenient_u64", default)]
+    pub avail_swap: u64,
+}
+
+impl MemoryUsage {
+    /// Used memory in bytes, as counted by `real_usage`
+    pub fn used_bytes(&self) -> u64 {
+        self.total_real.saturating_sub(self.avail_real + self.buffer + self.cached) * 1024
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.memory_size.max(self.total_real) * 1024
+    }
+
+    pub fn swap_used_bytes(&self) -> u64 {
+        self.total_swap.saturating_sub(self.avail_swap) * 1024
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NetworkRate {
+    /// Interface, `total` for the sum of all
+    pub device: String,
+    #[serde(deserialize_with = "de_lenient_u64", default)]
+    pub rx: u64,
+    #[serde(deserialize_with = "de_lenient_u64", default)]
+    pub tx: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DiskRates {
+    #[serde(default)]
+    pub total: Option<DiskRate>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DiskRate {
+    #[serde(deserialize_with = "de_lenient_u64", default)]
+    pub read_byte: u64,
+    #[serde(deserialize_with = "de_lenient_u64", default)]
+    pub write_byte: u64,
+    /// Busy time in percent
+    #[serde(deserialize_with = "de_lenient_u64", default)]
+    pub utilization: u64,
+}
+
+impl SystemUtilization {
+    /// Received and sent bytes per second over all interfaces
+    pub fn network_total(&self) -> (u64, u64) {
+        match self.network.iter().find(|n| n.device == "total") {
+            Some(total) => (total.rx, total.tx),
+            None => self.network.iter().fold((0, 0), |(rx, tx), n| (rx + n.rx, tx + n.tx)),
+        }
+    }
+}
+
+/// Response of SYNO.Core.CurrentConnection `list`.
 #[derive(Debug, Serialize, Deserialize)]
-pub struct ServiceStatusData {
-    #[serde(rename = "service_status", default)]
-    pub service_status: bool,
+pub struct ConnectionList {
+    #[serde(default)]
+    pub items: Vec<Connection>,
+}
 
-    // Alternative field names that might be in the response
-    #[serde(rename = "enable_ssh", alias = "enable", skip_serializing, default)]
-    pub enable_ssh: Option<bool>,
+impl From<ConnectionList> for Vec<Connection> {
+    fn from(data: ConnectionList) -> Self {
+        data.items
+    }
+}
+
+/// A user connected to DSM or a file service
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Connection {
+    #[serde(default)]
+    pub who: String,
+    /// Protocol, e.g. `HTTP/HTTPS`, `SMB`, `AFP`
+    #[serde(rename = "type", default)]
+    pub kind: String,
+    /// Client address
+    #[serde(default)]
+    pub from: String,
+}
 
-    #[serde(rename = "status", alias = "ssh_status", skip_serializing, default)]
-    pub status: Option<bool>,
+/// Response of SYNO.FileStation.BackgroundTask `list`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackgroundTaskList {
+    #[serde(default)]
+    pub tasks: Vec<BackgroundTask>,
 }
 
-impl From<ServiceStatusData> for bool {
-    fn from(data: ServiceStatusData) -> Self {
-        data.service_status 
-            || data.enable_ssh.unwrap_or(false) 
-            || data.status.unwrap_or(false)
+impl From<BackgroundTaskList> for Vec<BackgroundTask> {
+    fn from(data: BackgroundTaskList) -> Self {
+        data.tasks
+    }
+}
+
+/// A copy, move, delete or extract job of File Station
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackgroundTask {
+    /// API that started the task, e.g. `SYNO.FileStation.CopyMove`
+    #[serde(default)]
+    pub api: String,
+    #[serde(default)]
+    pub finished: bool,
+    /// Progress from 0 to 1
+    #[serde(default)]
+    pub progress: f64,
+}
+
+/// Response of SYNO.Backup.Task `list` (Hyper Backup).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupTaskList {
+    #[serde(default)]
+    pub task_list: Vec<BackupTask>,
+}
+
+impl From<BackupTaskList> for Vec<BackupTask> {
+    fn from(data: BackupTaskList) -> Self {
+        data.task_list
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupTask {
+    #[serde(default)]
+    pub name: String,
+    /// `none` when idle, e.g. `backup` while it runs
+    #[serde(default)]
+    pub status: String,
+}
+
+impl BackupTask {
+    pub fn is_running(&self) -> bool {
+        matches!(self.status.to_lowercase().as_str(), "backup" | "backingup" | "running" | "restore" | "detect" | "version_deleting")
     }
 }
 
@@ -160,7 +708,6 @@ pub struct SuccessResponse {
 impl From<SuccessResponse> for () {
     fn from(_: SuccessResponse) -> Self {
         // Simply return unit type as there's no data to convert
-        ()
     }
 }
 
@@ -170,10 +717,20 @@ pub struct SynologyClient {
     username: String,
     password: String,
     sid: Option<String>,
+    // Result of SYNO.API.Info, queried once per client
+    apis: Option<HashMap<String, ApiInfo>>,
+    // Device token that lets this client skip 2-step verification
+    device_id: Option<String>,
+    // Set when DSM asked for a 2-step verification code, with the chat that may answer
+    otp_requested: Option<(Instant, Option<i64>)>,
+    // Telegram chat the current requests are made for, `None` for background checks
+    requester: Option<i64>,
+    // Time of the last API request, for the idle logout
+    last_used: Instant,
 }
 
 impl SynologyClient {
-    pub fn new(base_url: &str, username: &str, password: &str, force_ipv4: bool) -> Self {
+    pub fn new(base_url: &str, username: &str, password: &str, force_ipv4: bool, tls: &TlsConfig) -> Self {
         // Create a client with cookie storage disabled and optionally force IPv4
         let mut client_builder = ClientBuilder::new()
             .cookie_store(false);
@@ -185,31 +742,54 @@ impl SynologyClient {
             debug!("Forcing IPv4 for Synology API requests");
         }
 
+        // Custom CA, client certificate, server name or fingerprint pinning (STB_SYNOLOGY_TLS_*)
+        let (client_builder, base_url) = tls.apply(client_builder, base_url);
+
         let client = client_builder
             .build()
             .expect("Failed to build reqwest client");
 
         SynologyClient {
             client,
-            base_url: base_url.to_string(),
+            base_url,
             username: username.to_string(),
             password: password.to_string(),
             sid: None,
+            apis: None,
+            device_id: None,
+            otp_requested: None,
+            requester: None,
+            last_used: Instant::now(),
         }
     }
 
+    /// Log out if the session has not been used for `idle_timeout`. Returns true if it logged out.
+    pub async fn logout_if_idle(&mut self, idle_timeout: Duration) -> bool {
+        if self.sid.is_none() || self.last_used.elapsed() < idle_timeout {
+            return false;
+        }
+        info!("Synology session idle for {} s", self.last_used.elapsed().as_secs());
+        if let Err(e) = self.logout().await {
+            // The session is dropped either way; DSM expires it on its own
+            error!("Failed to logout idle session: {}", e);
+            self.sid = None;
+        }
+        true
+    }
+
     pub(crate) async fn logout(&mut self) -> Result<(), SynologyClientError> {
         if self.sid.is_none() {
             debug!("Not logged in, no need to logout");
             return Ok(());
         }
 
-        let url = self.get_url(AUTH_ENDPOINT);
+        let (path, version) = self.resolve_api("SYNO.API.Auth", AUTH_MIN_VERSION, AUTH_MAX_VERSION).await?;
+        let url = self.get_url(&path);
         info!("Logging out from Synology NAS...");
 
         let params = [
                 ("api", "SYNO.API.Auth"),
-                ("version", "3"),
+                ("version", &version),
                 ("method", "logout"),
                 ("_sid", self.sid.as_ref().unwrap()),
             ];
@@ -238,17 +818,43 @@ impl SynologyClient {
     }
 
     pub(crate) async fn login(&mut self) -> Result<(), SynologyClientError> {
-        let url = self.get_url(AUTH_ENDPOINT);
+        self.login_with(None).await
+    }
 
-        info!("Logging in to Synology NAS...");
+    /// Log in with a 2-step verification code and ask DSM for a device token, so later logins
+    /// need no code. Returns the new device token.
+    pub async fn login_with_otp(&mut self, otp_code: &str) -> Result<String, SynologyClientError> {
+        self.login_with(Some(otp_code)).await?;
+        self.device_id.clone().ok_or_else(|| {
+            SynologyClientError::Generic("Login succeeded, but DSM returned no device token".to_string())
+        })
+    }
 
-        let params = [
+    async fn login_with(&mut self, otp_code: Option<&str>) -> Result<(), SynologyClientError> {
+        let (path, version) = self.resolve_api("SYNO.API.Auth", AUTH_MIN_VERSION, AUTH_MAX_VERSION).await?;
+        let url = self.get_url(&path);
+
+        info!("Logging in to Synology NAS{}...", if otp_code.is_some() { " with 2-step verification code" } else { "" });
+
+        let mut params = vec![
                 ("api", "SYNO.API.Auth"),
-                ("version", "3"),
+                ("version", version.as_str()),
                 ("method", "login"),
-                ("account", &self.username),
-                ("passwd", &self.password),
+                ("account", self.username.as_str()),
+                ("passwd", self.password.as_str()),
             ];
+        match (otp_code, &self.device_id) {
+            (Some(code), _) => {
+                params.push(("otp_code", code));
+                params.push(("enable_device_token", "yes"));
+                params.push(("device_name", DEVICE_NAME));
+            },
+            (None, Some(device_id)) => {
+                params.push(("device_name", DEVICE_NAME));
+                params.push(("device_id", device_id.as_str()));
+            },
+            (None, None) => {}
+        }
 
         let builder = self.client
             .get(&url)
@@ -257,7 +863,7 @@ impl SynologyClient {
         debug!("Synology request {:?}", builder);
 
         // Log the equivalent curl command
-        let curl_cmd = self.to_curl_command(&url, &params, &["passwd"]);
+        let curl_cmd = self.to_curl_command(&url, &params, &["passwd", "otp_code", "device_id"]);
         debug!("Equivalent curl command: {}", curl_cmd);
 
         let response = builder
@@ -269,25 +875,126 @@ impl SynologyClient {
 
         let auth_response: SynologyResponse<AuthData> = response.json().await?;
 
-        if auth_response.success {
-            if let Some(data) = auth_response.data {
-                self.sid = Some(data.sid);
-                info!("Successfully logged in to Synology NAS");
-                return Ok(());
+        if auth_response.success && let Some(data) = auth_response.data {
+            self.sid = Some(data.sid);
+            if otp_code.is_some() {
+                self.device_id = data.did;
             }
+            self.otp_requested = None;
+            self.last_used = Instant::now();
+            info!("Successfully logged in to Synology NAS");
+            return Ok(());
+        }
+
+        if auth_response.error.as_ref().is_some_and(|e| e.code == 403) {
+            // 2-step verification: a stored device token is missing or no longer valid
+            info!("Synology NAS asks for a 2-step verification code");
+            self.record_otp_prompt();
+            return Err(SynologyClientError::OtpRequired);
+        }
+
+        if auth_response.error.as_ref().is_some_and(|e| e.code == 406) {
+            warn!("Synology NAS enforces 2-step verification, but the account has not set it up");
+            return Err(SynologyClientError::OtpNotSetUp);
         }
 
         self.handle_error_response(auth_response.error, "Login failed")
     }
 
-    fn get_url(&mut self, endpoint: &str) -> String {
-        format!("{}/webapi{}", self.base_url, endpoint)
+    // The prompt belongs to the chat whose request hit it. A background check must not take
+    // over a prompt that a chat can still answer.
+    fn record_otp_prompt(&mut self) {
+        if self.requester.is_some() || !self.otp_pending() {
+            self.otp_requested = Some((Instant::now(), self.requester));
+        }
+    }
+
+    /// True while DSM is waiting for a 2-step verification code requested less than `OTP_PROMPT_TIMEOUT` ago.
+    pub fn otp_pending(&self) -> bool {
+        self.otp_requested.is_some_and(|(since, _)| since.elapsed() <= OTP_PROMPT_TIMEOUT)
+    }
+
+    /// True if the pending 2-step verification prompt was shown in `chat_id`, so a code from
+    /// that chat may answer it.
+    pub fn otp_pending_in(&self, chat_id: i64) -> bool {
+        self.otp_pending() && self.otp_requested.is_some_and(|(_, chat)| chat == Some(chat_id))
+    }
+
+    /// Set the Telegram chat the following requests are made for, `None` for background checks.
+    pub fn set_requester(&mut self, chat_id: Option<i64>) {
+        self.requester = chat_id;
+    }
+
+    pub fn set_device_id(&mut self, device_id: Option<String>) {
+        self.device_id = device_id;
+    }
+
+    fn get_url(&self, path: &str) -> String {
+        format!("{}/webapi/{}", self.base_url, path.trim_start_matches('/'))
+    }
+
+    // Query SYNO.API.Info once and cache path and supported versions of every API
+    async fn discover_apis(&mut self) -> Result<&HashMap<String, ApiInfo>, SynologyClientError> {
+        if self.apis.is_none() {
+            let url = self.get_url(INFO_PATH);
+            info!("Discovering Synology APIs...");
+
+            let params = [
+                ("api", "SYNO.API.Info"),
+                ("version", "1"),
+                ("method", "query"),
+                ("query", "all"),
+            ];
+
+            let builder = self.client
+                .get(&url)
+                .query(&params);
+            debug!("Synology request {:?}", builder);
+
+            // Log the equivalent curl command
+            let curl_cmd = self.to_curl_command(&url, &params, &[]);
+            debug!("Equivalent curl command: {}", curl_cmd);
+
+            let response = builder
+                .send()
+                .await?
+                .error_for_status()?;
+
+            let info_response: SynologyResponse<HashMap<String, ApiInfo>> = response.json().await?;
+            match info_response.data {
+                Some(apis) if info_response.success => {
+                    info!("Discovered {} Synology APIs", apis.len());
+                    self.apis = Some(apis);
+                },
+                _ => return self.handle_error_response(info_response.error, "API discovery failed"),
+            }
+        }
+
+        Ok(self.apis.as_ref().expect("API info cached above"))
+    }
+
+    // CGI path and best common version for an API, or UnsupportedApi before anything is sent to the NAS
+    async fn resolve_api(&mut self, api: &str, min_version: u32, max_version: u32) -> Result<(String, String), SynologyClientError> {
+        let apis = self.discover_apis().await?;
+        let info = apis.get(api).ok_or_else(|| SynologyClientError::UnsupportedApi {
+            api: api.to_string(),
+            reason: "API not offered by DSM".to_string(),
+        })?;
+        let version = info.best_version(min_version, max_version).ok_or_else(|| SynologyClientError::UnsupportedApi {
+            api: api.to_string(),
+            reason: format!(
+                "DSM offers versions {}-{}, the bot supports {}-{}",
+                info.min_version, info.max_version, min_version, max_version
+            ),
+        })?;
+        debug!("Using {} version {} at {}", api, version, info.path);
+        Ok((info.path.clone(), version.to_string()))
     }
 
     // Helper method to convert a request to its equivalent curl command
     fn to_curl_command(&self, url: &str, params: &[(&str, &str)], mask_params: &[&str]) -> String {
         // Start with the base curl command
-        let mut curl_cmd = format!("curl -X GET");
+        let mut curl_cmd = "curl -X GET".to_string();
 
         // Add the URL with query parameters
         let mut first_param = true;
@@ -322,12 +1029,37 @@ impl SynologyClient {
         Ok(self.sid.is_some())
     }
 
-    // Generic method to handle API requests
+    // Generic method to handle API requests. Reuses the session and logs in again once if DSM
+    // reports that the session expired or was replaced.
     async fn api_request<T, R>(
         &mut self, 
-        endpoint: &str, 
         api: &str, 
-        version: &str, 
+        min_version: u32, 
+        max_version: u32, 
+        method: &str, 
+        additional_params: Vec<(&str, &str)>,
+        operation_name: &str
+    ) -> Result<R, SynologyClientError> 
+    where 
+        T: for<'de> Deserialize<'de>,
+        R: From<T>
+    {
+        let result = self.api_request_once::<T, R>(api, min_version, max_version, method, additional_params.clone(), operation_name).await;
+        match result {
+            Err(SynologyClientError::Synology(ref err)) if SESSION_ERROR_CODES.contains(&err.code) => {
+                info!("Synology session no longer valid ({}), logging in again", err.get_error_description());
+                self.sid = None;
+                self.api_request_once::<T, R>(api, min_version, max_version, method, additional_params, operation_name).await
+            },
+            result => result,
+        }
+    }
+
+    async fn api_request_once<T, R>(
+        &mut self, 
+        api: &str, 
+        min_version: u32, 
+        max_version: u32, 
         method: &str, 
         additional_params: Vec<(&str, &str)>,
         operation_name: &str
@@ -336,17 +1068,20 @@ impl SynologyClient {
         T: for<'de> Deserialize<'de>,
         R: From<T>
     {
+        let (path, version) = self.resolve_api(api, min_version, max_version).await?;
+        self.last_used = Instant::now();
+
         if !self.ensure_login().await? {
             error!("Login attempt failed. Cannot {}.", operation_name);
             return Err(SynologyClientError::LoginFailed);
         }
 
-        let url = self.get_url(endpoint);
+        let url = self.get_url(&path);
 
         // Build base query parameters
         let mut params = vec![
             ("api", api),
-            ("version", version),
+            ("version", &version),
             ("method", method),
             ("_sid", self.sid.as_ref().unwrap()),
         ];
@@ -381,8 +1116,12 @@ impl SynologyClient {
         };
 
         if api_response.success {
-            if let Some(data) = api_response.data {
-                return Ok(data.into());
+            match api_response.data {
+                Some(data) => return Ok(data.into()),
+                // Responses like {"success":true} carry no data; accept them if T can be built from an empty object
+                None => if let Ok(data) = serde_json::from_value::<T>(serde_json::json!({})) {
+                    return Ok(data.into());
+                }
             }
         }
 
@@ -402,102 +1141,334 @@ impl SynologyClient {
         }
     }
 
-    pub async fn get_ssh_status(&mut self) -> Result<bool, SynologyClientError> {
-        // Explicitly login before the request
-        self.login().await?;
-
-        // Use a match to ensure logout happens even if there's an error
-        let api_result = self.api_request::<ServiceStatusData, bool>(
-            TERMINAL_ENDPOINT,
-            "SYNO.Core.Terminal",
-            "1",
-            "get",
+    // Read the on/off state of a service; the caller takes care of the session
+    async fn fetch_service_status(&mut self, service: &ServiceDef) -> Result<bool, SynologyClientError> {
+        let data = self.api_request::<serde_json::Value, serde_json::Value>(
+            service.api,
+            service.min_version,
+            service.max_version,
+            service.get_method,
             vec![],
-            "get SSH service status"
-        ).await;
+            &format!("get {} service status", service.label)
+        ).await?;
 
-        // Always logout after the request
-        if let Err(e) = self.logout().await {
-            error!("Failed to logout after get_ssh_status: {}", e);
-        }
+        service.status_from(&data).ok_or_else(|| {
+            let error_msg = format!("{} status not found in response (expected one of {:?})", service.label, service.status_fields);
+            error!("{}", error_msg);
+            SynologyClientError::Generic(error_msg)
+        })
+    }
 
-        // Process the result
-        let result = api_result?;
-        info!("SSH service status: {}", if result { "enabled" } else { "disabled" });
+    pub async fn get_service_status(&mut self, service: &ServiceDef) -> Result<bool, SynologyClientError> {
+        let result = self.fetch_service_status(service).await?;
+        info!("{} service status: {}", service.label, if result { "enabled" } else { "disabled" });
         Ok(result)
     }
 
-    pub async fn toggle_ssh(&mut self, enable: bool) -> Result<(), SynologyClientError> {
-        info!("Toggle SSH service ({}) ...", if enable { "Enabling" } else { "Disabling" });
+    /// Status of every known service. Fails as a whole only if the login fails.
+    pub async fn get_all_service_status(&mut self) -> Result<Vec<(&'static ServiceDef, Result<bool, SynologyClientError>)>, SynologyClientError> {
+        self.ensure_login().await?;
 
-        // Explicitly login before the request
-        self.login().await?;
+        let mut statuses = Vec::with_capacity(SERVICES.len());
+        for service in SERVICES {
+            statuses.push((service, self.fetch_service_status(service).await));
+        }
 
-        let enable_ssh_new_state = if enable { "true" } else { "false" };
-        let operation_name = format!("{} SSH service", if enable { "enable" } else { "disable" });
+        Ok(statuses)
+    }
 
-        // Build the URL and parameters
-        let url = self.get_url(TERMINAL_ENDPOINT);
-        let params = vec![
-            ("api", "SYNO.Core.Terminal"),
-            ("version", "1"),
-            ("method", "set"),
-            ("enable_ssh", enable_ssh_new_state),
-            ("_sid", self.sid.as_ref().unwrap()),
-        ];
+    pub async fn set_service(&mut self, service: &ServiceDef, enable: bool) -> Result<(), SynologyClientError> {
+        info!("Toggle {} service ({}) ...", service.label, if enable { "Enabling" } else { "Disabling" });
 
-        // Log the request
-        let builder = self.client.get(&url).query(&params);
-        debug!("Synology request {:?}", builder);
+        self.api_request::<SuccessResponse, ()>(
+            service.api,
+            service.min_version,
+            service.max_version,
+            service.set_method,
+            vec![(service.enable_param, if enable { "true" } else { "false" })],
+            &format!("{} {} service", if enable { "enable" } else { "disable" }, service.label)
+        ).await?;
 
-        // Log the equivalent curl command
-        let curl_cmd = self.to_curl_command(&url, &params, &[]);
-        debug!("Equivalent curl command: {}", curl_cmd);
+        info!("Successfully {} {} service", if enable { "enabled" } else { "disabled" }, service.label);
+        Ok(())
+    }
 
-        // Send request
-        let response = builder
-            .send()
-            .await?
-            .error_for_status()?;
+    /// Volumes, storage pools and disks with their health.
+    pub async fn get_storage_info(&mut self) -> Result<StorageInfo, SynologyClientError> {
+        self.api_request::<StorageInfo, StorageInfo>(
+            "SYNO.Storage.CGI.Storage",
+            1,
+            1,
+            "load_info",
+            vec![],
+            "load storage info"
+        ).await
+    }
 
-        let body_text = response.text().await?;
-        debug!("Response body: {}", body_text);
+    /// Model, DSM version, serial number, uptime and temperature.
+    pub async fn get_system_info(&mut self) -> Result<SystemInfo, SynologyClientError> {
+        self.api_request::<SystemInfo, SystemInfo>(
+            "SYNO.Core.System",
+            1,
+            3,
+            "info",
+            vec![],
+            "load system info"
+        ).await
+    }
 
-        // Parse response
-        let json_value: serde_json::Value = serde_json::from_str(&body_text)
-            .map_err(|e| SynologyClientError::Generic(format!("JSON parsing error: {}", e)))?;
-
-        // Check if the response has a success field and it's true
-        if let Some(success) = json_value.get("success").and_then(|v| v.as_bool()) {
-            if success {
-                // Always logout after the request
-                if let Err(e) = self.logout().await {
-                    error!("Failed to logout after toggle_ssh: {}", e);
-                }
+    /// Current CPU, memory, network and disk load.
+    pub async fn get_utilization(&mut self) -> Result<SystemUtilization, SynologyClientError> {
+        self.api_request::<SystemUtilization, SystemUtilization>(
+            "SYNO.Core.System.Utilization",
+            1,
+            1,
+            "get",
+            vec![],
+            "load system utilization"
+        ).await
+    }
 
-                info!("Successfully {} SSH service", if enable { "enabled" } else { "disabled" });
-                return Ok(());
-            }
-        }
+    /// Users connected to DSM and the file services.
+    pub async fn get_connections(&mut self) -> Result<Vec<Connection>, SynologyClientError> {
+        self.api_request::<ConnectionList, Vec<Connection>>(
+            "SYNO.Core.CurrentConnection",
+            1,
+            1,
+            "list",
+            vec![],
+            "list connections"
+        ).await
+    }
 
-        // If we get here, the request was not successful
-        // Always logout after the request
-        if let Err(e) = self.logout().await {
-            error!("Failed to logout after toggle_ssh: {}", e);
-        }
+    /// File Station copy, move and delete jobs, finished ones included.
+    pub async fn get_background_tasks(&mut self) -> Result<Vec<BackgroundTask>, SynologyClientError> {
+        self.api_request::<BackgroundTaskList, Vec<BackgroundTask>>(
+            "SYNO.FileStation.BackgroundTask",
+            1,
+            3,
+            "list",
+            vec![],
+            "list background tasks"
+        ).await
+    }
 
-        // Handle error response
-        if let Some(error) = json_value.get("error").and_then(|v| v.as_object()) {
-            if let Some(code_val) = error.get("code").and_then(|v| v.as_i64()) {
-                let error_msg = format!("{} failed with error code: {}", operation_name, code_val);
-                error!("{}", error_msg);
-                return Err(SynologyClientError::Generic(error_msg));
-            }
-        }
+    /// Hyper Backup tasks with their state; fails if Hyper Backup is not installed.
+    pub async fn get_backup_tasks(&mut self) -> Result<Vec<BackupTask>, SynologyClientError> {
+        self.api_request::<BackupTaskList, Vec<BackupTask>>(
+            "SYNO.Backup.Task",
+            1,
+            1,
+            "list",
+            vec![("additional", r#"["status"]"#)],
+            "list backup tasks"
+        ).await
+    }
+
+    /// One page of the shared folders, sorted by name.
+    pub async fn list_shares(&mut self, offset: u32, limit: u32) -> Result<FileListData, SynologyClientError> {
+        let offset = offset.to_string();
+        let limit = limit.to_string();
+        self.api_request::<FileListData, FileListData>(
+            "SYNO.FileStation.List",
+            1,
+            2,
+            "list_share",
+            vec![("offset", &offset), ("limit", &limit), ("sort_by", "name"), ("additional", r#"["time"]"#)],
+            "list shared folders"
+        ).await
+    }
+
+    /// One page of the files and subfolders of `path`, sorted by name.
+    pub async fn list_folder(&mut self, path: &str, offset: u32, limit: u32) -> Result<FileListData, SynologyClientError> {
+        let offset = offset.to_string();
+        let limit = limit.to_string();
+        self.api_request::<FileListData, FileListData>(
+            "SYNO.FileStation.List",
+            1,
+            2,
+            "list",
+            vec![
+                ("folder_path", path),
+                ("offset", &offset),
+                ("limit", &limit),
+                ("sort_by", "name"),
+                ("additional", r#"["size","time"]"#),
+            ],
+            "list folder"
+        ).await
+    }
+
+    /// Shut the NAS down (`reboot = false`) or restart it. DSM answers before it goes down.
+    pub async fn power_off(&mut self, reboot: bool) -> Result<(), SynologyClientError> {
+        let method = if reboot { "reboot" } else { "shutdown" };
+        info!("Requesting DSM {} ...", method);
+
+        self.api_request::<SuccessResponse, ()>(
+            "SYNO.Core.System",
+            1,
+            1,
+            method,
+            vec![("force", "false"), ("local", "true")],
+            method
+        ).await?;
+
+        info!("DSM accepted the {} request", method);
+        Ok(())
+    }
+
+    pub async fn get_ssh_status(&mut self) -> Result<bool, SynologyClientError> {
+        self.get_service_status(&SSH_SERVICE).await
+    }
+
+    pub async fn toggle_ssh(&mut self, enable: bool) -> Result<(), SynologyClientError> {
+        self.set_service(&SSH_SERVICE, enable).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn otp_prompt_belongs_to_the_requesting_chat() {
+        let mut client = SynologyClient::new("https://nas.example", "admin", "secret", false, &TlsConfig::default());
+        client.set_requester(Some(1));
+        client.record_otp_prompt();
+        assert!(client.otp_pending_in(1));
+        assert!(!client.otp_pending_in(2));
+
+        // The monitor hitting the prompt again leaves it with the chat
+        client.set_requester(None);
+        client.record_otp_prompt();
+        assert!(client.otp_pending_in(1));
+
+        // Another chat asking later takes it over
+        client.set_requester(Some(2));
+        client.record_otp_prompt();
+        assert!(client.otp_pending_in(2));
+        assert!(!client.otp_pending_in(1));
+    }
+
+    #[test]
+    fn maps_service_status_per_service() {
+        let terminal = serde_json::json!({ "enable_ssh": true, "enable_telnet": false, "ssh_port": 22 });
+        assert_eq!(SSH_SERVICE.status_from(&terminal), Some(true));
+        assert_eq!(find_service("telnet").unwrap().status_from(&terminal), Some(false));
+
+        let smb = serde_json::json!({ "enable_samba": "yes", "workgroup": "WORKGROUP" });
+        assert_eq!(find_service("samba").unwrap().status_from(&smb), Some(true));
+
+        let nfs = serde_json::json!({ "enable_nfs": 0 });
+        assert_eq!(find_service("NFS").unwrap().status_from(&nfs), Some(false));
+
+        assert_eq!(find_service("ftp").unwrap().status_from(&terminal), None);
+    }
+
+    #[test]
+    fn picks_best_common_api_version() {
+        let info: SynologyResponse<HashMap<String, ApiInfo>> = serde_json::from_str(r#"{
+            "data": {
+                "SYNO.API.Auth": { "maxVersion": 7, "minVersion": 1, "path": "entry.cgi" },
+                "SYNO.Core.FileServ.NFS": { "maxVersion": 3, "minVersion": 3, "path": "entry.cgi", "requestFormat": "JSON" }
+            },
+            "success": true
+        }"#).unwrap();
+        let apis = info.data.unwrap();
+
+        assert_eq!(apis["SYNO.API.Auth"].best_version(AUTH_MIN_VERSION, AUTH_MAX_VERSION), Some(6));
+        assert_eq!(apis["SYNO.API.Auth"].best_version(1, 2), Some(2));
+        assert_eq!(apis["SYNO.Core.FileServ.NFS"].request_format.as_deref(), Some("JSON"));
+        // NAS only speaks version 3, the NFS definition stops at 2
+        assert_eq!(apis["SYNO.Core.FileServ.NFS"].best_version(1, 2), None);
+    }
+
+    #[test]
+    fn parses_storage_info() {
+        let info: StorageInfo = serde_json::from_str(r#"{
+            "disks": [
+                { "id": "sata1", "name": "Drive 1", "model": "WD40EFRX", "status": "normal", "smart_status": "normal", "temp": 34, "size_total": "4000787030016" },
+                { "id": "sata2", "name": "Drive 2", "model": "WD40EFRX", "status": "crashed", "smart_status": "failing", "temp": 41, "size_total": 4000787030016 }
+            ],
+            "storagePools": [
+                { "id": "reuse_1", "num_id": 1, "status": "degraded", "device_type": "raid_1", "size": { "total": "3996329328640", "used": "3000000000000" }, "disks": ["sata1", "sata2"] }
+            ],
+            "volumes": [
+                { "id": "volume_1", "vol_path": "/volume1", "status": "normal", "fs_type": "btrfs", "pool_path": "reuse_1", "size": { "total": "1000", "used": "250", "free_inode": "0" } }
+            ]
+        }"#).unwrap();
+
+        assert_eq!(info.volumes[0].size.used_percent(), 25.0);
+        assert_eq!(info.disks[1].size_total, 4000787030016);
+        assert!(info.disks[0].smart_ok());
+        assert!(!info.disks[1].smart_ok());
+        assert!(info.storage_pools[0].is_degraded());
+        assert!(!info.storage_pools[0].is_crashed());
+        assert_eq!(info.storage_pools[0].raid_label(), "RAID 1");
+    }
+
+    #[test]
+    fn parses_system_info_and_utilization() {
+        let info: SystemInfo = serde_json::from_str(r#"{
+            "model": "DS923+", "firmware_ver": "DSM 7.2-64570 Update 1", "serial": "2260ABC", "up_time": "66:59:37",
+            "sys_temp": 45, "temperature_warning": false, "time": "2024-05-01 10:04:09", "cpu_cores": "4"
+        }"#).unwrap();
+        assert_eq!(info.uptime(), Some(Duration::from_secs(66 * 3600 + 59 * 60 + 37)));
+        assert_eq!(info.sys_temp, Some(45));
+
+        let util: SystemUtilization = serde_json::from_str(r#"{
+            "cpu": { "1min_load": 12, "5min_load": 20, "15min_load": 15, "user_load": 5, "system_load": 2, "other_load": 1, "device": "System" },
+            "memory": { "real_usage": 20, "memory_size": 4194304, "total_real": 3900000, "avail_real": 2800000, "buffer": 100000, "cached": 300000,
+                        "swap_usage": 0, "total_swap": 2097152, "avail_swap": 2097152 },
+            "network": [ { "device": "total", "rx": 1200, "tx": "560" }, { "device": "eth0", "rx": 1200, "tx": 560 } ],
+            "disk": { "disk": [], "total": { "device": "total", "read_byte": 3400, "write_byte": 1000, "utilization": 3 } }
+        }"#).unwrap();
+        assert_eq!(util.cpu.percent(), 8);
+        assert_eq!(util.memory.used_bytes(), 700000 * 1024);
+        assert_eq!(util.memory.swap_used_bytes(), 0);
+        assert_eq!(util.network_total(), (1200, 560));
+        assert_eq!(util.disk.total.as_ref().map(|d| d.read_byte), Some(3400));
+    }
+
+    #[test]
+    fn parses_preflight_lists() {
+        let connections: SynologyResponse<ConnectionList> = serde_json::from_str(r#"{
+            "data": { "items": [ { "who": "admin", "type": "HTTP/HTTPS", "from": "192.168.1.5", "descr": "DSM", "can_be_kicked": false } ], "total": 1 },
+            "success": true
+        }"#).unwrap();
+        let connections: Vec<Connection> = connections.data.unwrap().into();
+        assert_eq!(connections[0].kind, "HTTP/HTTPS");
+
+        let tasks: BackgroundTaskList = serde_json::from_str(r#"{ "tasks": [ { "api": "SYNO.FileStation.CopyMove", "finished": false, "progress": 0.5 } ] }"#).unwrap();
+        assert!(!tasks.tasks[0].finished);
+
+        let backups: BackupTaskList = serde_json::from_str(r#"{ "task_list": [ { "task_id": 1, "name": "Cloud", "status": "backup" }, { "name": "USB", "status": "none" } ] }"#).unwrap();
+        assert!(backups.task_list[0].is_running());
+        assert!(!backups.task_list[1].is_running());
+    }
+
+    #[test]
+    fn parses_file_lists() {
+        let shares: FileListData = serde_json::from_str(r#"{ "offset": 0, "total": 1, "shares": [ { "isdir": true, "name": "photo", "path": "/photo" } ] }"#).unwrap();
+        assert_eq!(shares.files[0].path, "/photo");
+        assert!(shares.files[0].additional.size.is_none());
+
+        let files: FileListData = serde_json::from_str(r#"{ "offset": 50, "total": 51, "files": [ {
+            "isdir": false, "name": "a.txt", "path": "/photo/a.txt",
+            "additional": { "size": 1024, "time": { "atime": 1700000300, "crtime": 1700000000, "ctime": 1700000100, "mtime": 1700000200 } }
+        } ] }"#).unwrap();
+        assert_eq!((files.offset, files.total), (50, 51));
+        assert_eq!(files.files[0].additional.size, Some(1024));
+        assert_eq!(files.files[0].additional.time.as_ref().unwrap().mtime, 1700000200);
+    }
 
-        // Generic error if we can't parse the error
-        let error_msg = format!("{} failed with unknown error", operation_name);
-        error!("{}", error_msg);
-        Err(SynologyClientError::Generic(error_msg))
+    #[test]
+    fn service_names_are_unique_and_callback_safe() {
+        let mut names: Vec<&str> = SERVICES.iter().flat_map(|s| std::iter::once(s.name).chain(s.aliases.iter().copied())).collect();
+        let count = names.len();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), count);
+        assert!(names.iter().all(|n| n.chars().all(|c| c.is_ascii_lowercase())));
     }
 }
@@ -0,0 +1,227 @@
+use std::fmt;
+use std::net::ToSocketAddrs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::SystemTime;
+use reqwest::{ClientBuilder, Url};
+use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+use rustls::{Certificate, ClientConfig, PrivateKey, RootCertStore, ServerName};
+use sha2::{Digest, Sha256};
+use log::{info, warn};
+
+use crate::config::Settings;
+
+#[derive(Debug)]
+pub struct TlsError(String);
+
+impl fmt::Display for TlsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TLS configuration error: {}", self.0)
+    }
+}
+
+impl std::error::Error for TlsError {}
+
+/// TLS settings for one HTTPS endpoint (OpenBao or DSM), read from `<PREFIX>_TLS_*`.
+/// Without any setting the HTTP client keeps its default TLS behaviour.
+#[derive(Clone, Default)]
+pub struct TlsConfig {
+    // Built once at startup, so broken files are reported before the first request
+    rustls: Option<Arc<ClientConfig>>,
+    server_name: Option<String>,
+}
+
+impl TlsConfig {
+    /// Reads `<PREFIX>_TLS_CA` (PEM bundle), `<PREFIX>_TLS_CLIENT_CERT` and `<PREFIX>_TLS_CLIENT_KEY`
+    /// (PEM, for mTLS), `<PREFIX>_TLS_SERVER_NAME` and `<PREFIX>_TLS_FINGERPRINT` (SHA-256 of the
+    /// server certificate; replaces the CA check, e.g. for a self-signed DSM certificate).
+    pub fn from_settings(settings: &Settings, prefix: &str) -> Result<Self, TlsError> {
+        let var = |name: &str| settings.get(&format!("{}_TLS_{}", prefix, name));
+        let ca = var("CA");
+        let client_cert = var("CLIENT_CERT");
+        let client_key = var("CLIENT_KEY");
+        let fingerprint = match var("FINGERPRINT") {
+            Some(value) => Some(parse_fingerprint(&value).ok_or_else(|| {
+                TlsError(format!("{}_TLS_FINGERPRINT '{}' is not a SHA-256 fingerprint", prefix, value))
+            })?),
+            None => None,
+        };
+
+        let rustls = if ca.is_some() || client_cert.is_some() || client_key.is_some() || fingerprint.is_some() {
+            let config = build_client_config(
+                ca.as_deref().map(Path::new),
+                client_cert.as_deref().map(Path::new),
+                client_key.as_deref().map(Path::new),
+                fingerprint
+            )?;
+            info!(
+                "{}: custom TLS configuration (CA bundle: {}, client certificate: {}, fingerprint pinning: {})",
+                prefix,
+                ca.as_deref().unwrap_or("system"),
+                client_cert.as_deref().unwrap_or("none"),
+                if fingerprint.is_some() { "on" } else { "off" }
+            );
+            Some(Arc::new(config))
+        } else {
+            None
+        };
+
+        Ok(TlsConfig { rustls, server_name: var("SERVER_NAME") })
+    }
+
+    /// Apply the settings to a client for `base_url`. Returns the builder and the URL to send
+    /// requests to: with a server name override the URL carries that name (for SNI and the
+    /// certificate check) while the connection still goes to the configured address.
+    pub fn apply(&self, mut builder: ClientBuilder, base_url: &str) -> (ClientBuilder, String) {
+        if let Some(config) = &self.rustls {
+            builder = builder.use_preconfigured_tls((**config).clone());
+        }
+        let Some(server_name) = &self.server_name else {
+            return (builder, base_url.to_string());
+        };
+        match override_server_name(base_url, server_name) {
+            Ok((url, addrs)) => (builder.resolve_to_addrs(server_name, &addrs), url),
+            Err(e) => {
+                warn!("TLS server name override for {} not applied: {}", base_url, e);
+                (builder, base_url.to_string())
+            }
+        }
+    }
+}
+
+// Replace the host of `base_url` by `server_name` and resolve the original host
+fn override_server_name(base_url: &str, server_name: &str) -> Result<(String, Vec<std::net::SocketAddr>), String> {
+    let mut url = Url::parse(base_url).map_err(|e| e.to_string())?;
+    let host = url.host_str().ok_or("URL has no host")?.trim_matches(['[', ']']).to_string();
+    let port = url.port_or_known_default().ok_or("URL has no port")?;
+    let addrs: Vec<_> = (host.as_str(), port)
+        .to_socket_addrs()
+        .map_err(|e| format!("cannot resolve {}: {}", host, e))?
+        .collect();
+    url.set_host(Some(server_name)).map_err(|e| e.to_string())?;
+    Ok((url.as_str().trim_end_matches('/').to_string(), addrs))
+}
+
+fn build_client_config(
+    ca: Option<&Path>,
+    client_cert: Option<&Path>,
+    client_key: Option<&Path>,
+    fingerprint: Option<[u8; 32]>
+) -> Result<ClientConfig, TlsError> {
+    let builder = ClientConfig::builder().with_safe_defaults();
+    let builder = match fingerprint {
+        Some(fingerprint) => builder.with_custom_certificate_verifier(Arc::new(PinnedCertVerifier { fingerprint })),
+        None => {
+            let mut roots = RootCertStore::empty();
+            let certs: Vec<Vec<u8>> = match ca {
+                Some(path) => read_certs(path)?.into_iter().map(|c| c.0).collect(),
+                None => rustls_native_certs::load_native_certs()
+                    .map_err(|e| TlsError(format!("cannot load system certificates: {}", e)))?
+                    .into_iter()
+                    .map(|c| c.0)
+                    .collect(),
+            };
+            let (added, _) = roots.add_parsable_certificates(&certs);
+            if added == 0 {
+                return Err(TlsError(format!("no usable CA certificate in {}", ca.map_or("the system store".into(), |p| p.display().to_string()))));
+            }
+            // Same verifier `with_root_certificates` would install, so both arms share one type
+            builder.with_custom_certificate_verifier(Arc::new(WebPkiVerifier::new(roots, None)))
+        }
+    };
+    match (client_cert, client_key) {
+        (Some(cert), Some(key)) => builder
+            .with_client_auth_cert(read_certs(cert)?, read_key(key)?)
+            .map_err(|e| TlsError(format!("invalid client certificate or key: {}", e))),
+        (None, None) => Ok(builder.with_no_client_auth()),
+        _ => Err(TlsError("client certificate and key must be set together".to_string())),
+    }
+}
+
+fn read_certs(path: &Path) -> Result<Vec<Certificate>, TlsError> {
+    let pem = std::fs::read(path).map_err(|e| TlsError(format!("cannot read {}: {}", path.display(), e)))?;
+    let certs = rustls_pemfile::certs(&mut pem.as_slice())
+        .map_err(|e| TlsError(format!("invalid PEM in {}: {}", path.display(), e)))?;
+    if certs.is_empty() {
+        return Err(TlsError(format!("no certificate in {}", path.display())));
+    }
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+fn read_key(path: &Path) -> Result<PrivateKey, TlsError> {
+    use rustls_pemfile::Item;
+
+    // The key is a secret like any other: no world-readable files
+    let pem = crate::secrets::read_secret_file(path).map_err(|e| TlsError(e.to_string()))?;
+    let items = rustls_pemfile::read_all(&mut pem.as_bytes())
+        .map_err(|e| TlsError(format!("invalid PEM in {}: {}", path.display(), e)))?;
+    items
+        .into_iter()
+        .find_map(|item| match item {
+            Item::PKCS8Key(key) | Item::RSAKey(key) | Item::ECKey(key) => Some(PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or_else(|| TlsError(format!("no private key in {}", path.display())))
+}
+
+/// SHA-256 fingerprint as shown by browsers or `openssl x509 -fingerprint -sha256`
+/// (hex, colons and a `sha256:` prefix are optional).
+pub fn parse_fingerprint(value: &str) -> Option<[u8; 32]> {
+    let value = value.trim();
+    let value = value.strip_prefix("sha256:").or_else(|| value.strip_prefix("SHA256:")).unwrap_or(value);
+    let hex: String = value.chars().filter(|c| *c != ':' && !c.is_whitespace()).collect();
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+    let mut fingerprint = [0u8; 32];
+    for (i, byte) in fingerprint.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(fingerprint)
+}
+
+// Accepts exactly the server certificate with the pinned fingerprint, whatever signed it.
+// The handshake signatures are still checked by rustls.
+struct PinnedCertVerifier {
+    fingerprint: [u8; 32],
+}
+
+impl ServerCertVerifier for PinnedCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if Sha256::digest(&end_entity.0).as_slice() == self.fingerprint {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::General("server certificate does not match the pinned fingerprint".to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_fingerprints() {
+        let expected: [u8; 32] = core::array::from_fn(|i| i as u8 * 8);
+        let hex: String = expected.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(":");
+        assert_eq!(parse_fingerprint(&hex), Some(expected));
+        assert_eq!(parse_fingerprint(&format!("sha256:{}", hex.replace(':', "").to_lowercase())), Some(expected));
+        assert_eq!(parse_fingerprint("AB:CD"), None);
+        assert_eq!(parse_fingerprint(&"zz".repeat(32)), None);
+    }
+
+    #[test]
+    fn server_name_override_keeps_address() {
+        let (url, addrs) = override_server_name("https://10.0.0.5:5001", "nas.internal").unwrap();
+        assert_eq!(url, "https://nas.internal:5001");
+        assert_eq!(addrs, vec!["10.0.0.5:5001".parse().unwrap()]);
+    }
+}
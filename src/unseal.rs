@@ -0,0 +1,544 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use teloxide::prelude::*;
+use teloxide::types::{ButtonRequest, InlineKeyboardButton, InlineKeyboardMarkup, KeyboardButton, KeyboardMarkup, MessageId, ReplyMarkup, UserId, WebAppInfo};
+use teloxide::RequestError;
+use tokio::sync::Mutex;
+use log::{error, info, warn};
+
+use crate::bao::{decrypt_ciphertext, generate_ephemeral_key, is_age_ciphertext, random_session_id, BaoError, SealStatusInfo};
+use crate::ssh_timer::format_duration;
+use crate::web::MINI_APP_PATH;
+use crate::{BaoConfig, CALLBACK_UNSEAL_JOIN};
+
+/// How long an unseal session accepts shares
+pub const UNSEAL_SESSION_TIMEOUT: Duration = Duration::from_secs(300);
+/// How often the sweeper looks for expired sessions
+const SWEEP_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Collective unseal: the chat that ran `/unseal` follows the progress, every key holder who
+/// joins gets an own ephemeral keypair and link, so each share is encrypted for the bot alone
+/// and never visible to the other participants. Lives only in RAM.
+pub struct UnsealSession {
+    /// Chat where the session was started and the progress is posted
+    pub chat_id: ChatId,
+    pub session_id: String,
+    pub expires_at: Instant,
+    /// Progress message in `chat_id`, edited after every share
+    pub status_message: Option<MessageId>,
+    /// Shares OpenBao has accepted so far
+    pub progress: u32,
+    /// Shares needed to unseal (`t`)
+    pub threshold: u32,
+    holders: Vec<KeyHolder>,
+}
+
+struct KeyHolder {
+    user_id: UserId,
+    name: String,
+    /// Reference used in the holder's link
+    share_id: String,
+    /// Dropped after the first ciphertext; a new link has to be requested after a failure
+    identity: Option<age::x25519::Identity>,
+    issued: Instant,
+    submitted: bool,
+}
+
+/// Link data for a key holder who joined the session
+pub struct ShareLink {
+    pub share_id: String,
+    pub recipient: String,
+}
+
+impl UnsealSession {
+    pub fn new(chat_id: ChatId, progress: u32, threshold: u32, timeout: Duration) -> Self {
+        UnsealSession {
+            chat_id,
+            session_id: random_session_id(),
+            expires_at: Instant::now() + timeout,
+            status_message: None,
+            progress,
+            threshold,
+            holders: Vec::new(),
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        Instant::now() >= self.expires_at
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.expires_at.saturating_duration_since(Instant::now())
+    }
+
+    /// Issue a fresh keypair for a key holder. Joining again replaces the earlier link;
+    /// `None` if the holder has already submitted a share.
+    pub fn join(&mut self, user_id: UserId, name: &str) -> Option<ShareLink> {
+        let key = generate_ephemeral_key();
+        let share_id = random_session_id();
+        match self.holders.iter_mut().find(|h| h.user_id == user_id) {
+            Some(holder) if holder.submitted => return None,
+            Some(holder) => {
+                holder.share_id = share_id.clone();
+                holder.identity = Some(key.identity);
+                holder.issued = Instant::now();
+            },
+            None => self.holders.push(KeyHolder {
+                user_id,
+                name: name.to_string(),
+                share_id: share_id.clone(),
+                identity: Some(key.identity),
+                issued: Instant::now(),
+                submitted: false,
+            }),
+        }
+        Some(ShareLink { share_id, recipient: key.recipient })
+    }
+
+    // When the user's unused link was issued, `None` if there is none
+    fn pending_link_issued(&self, user_id: UserId) -> Option<Instant> {
+        self.holders
+            .iter()
+            .find(|h| h.user_id == user_id && h.identity.is_some())
+            .map(|h| h.issued)
+    }
+
+    /// Key holders whose link has not been used yet
+    pub fn pending_holders(&self) -> impl Iterator<Item = UserId> + '_ {
+        self.holders.iter().filter(|h| h.identity.is_some()).map(|h| h.user_id)
+    }
+
+    /// Decrypt the share sent by a key holder. The identity is used once: after a failed
+    /// decryption the holder has to request a new link.
+    pub fn decrypt_share(&mut self, user_id: UserId, ciphertext: &[u8]) -> Option<Result<String, BaoError>> {
+        let holder = self.holders.iter_mut().find(|h| h.user_id == user_id)?;
+        let identity = holder.identity.take()?;
+        let result = decrypt_ciphertext(ciphertext, &identity);
+        if result.is_ok() {
+            holder.submitted = true;
+        }
+        Some(result)
+    }
+
+    /// Name of a key holder, for progress messages
+    pub fn holder_name(&self, user_id: UserId) -> Option<&str> {
+        self.holders.iter().find(|h| h.user_id == user_id).map(|h| h.name.as_str())
+    }
+
+    /// Progress message for the session chat: shares accepted and who has taken part, never the shares
+    pub fn status_text(&self) -> String {
+        let mut text = format!("🔐 OpenBao unseal: {}/{} shares\n", self.progress, self.threshold);
+        if !self.holders.is_empty() {
+            text.push('\n');
+            for holder in &self.holders {
+                let mark = if holder.submitted { "✅" } else { "⏳" };
+                text.push_str(&format!("{} {}\n", mark, holder.name));
+            }
+        }
+        text.push_str(&format!(
+            "\nKey holders: press the button to get your personal link in a private chat with the bot (session expires in {}).",
+            format_duration(self.remaining())
+        ));
+        text
+    }
+
+    /// The progress message as it should look now, `None` if the session has none
+    pub fn status_update(&self) -> Option<StatusUpdate> {
+        Some(StatusUpdate {
+            chat_id: self.chat_id,
+            message_id: self.status_message?,
+            session_id: self.session_id.clone(),
+            text: self.status_text(),
+        })
+    }
+}
+
+/// A progress message to edit, taken out of the session store so the store is not locked
+/// while Telegram is called
+pub struct StatusUpdate {
+    chat_id: ChatId,
+    message_id: MessageId,
+    session_id: String,
+    text: String,
+}
+
+/// Pending unseal sessions by session ID. Every chat can run its own session; sessions of
+/// different chats coexist and expire independently.
+#[derive(Default)]
+pub struct UnsealSessions {
+    sessions: HashMap<String, UnsealSession>,
+}
+
+impl UnsealSessions {
+    pub fn insert(&mut self, session: UnsealSession) {
+        self.sessions.insert(session.session_id.clone(), session);
+    }
+
+    /// Live session with this ID
+    pub fn get_mut(&mut self, session_id: &str) -> Option<&mut UnsealSession> {
+        self.sessions.get_mut(session_id).filter(|s| !s.is_expired())
+    }
+
+    /// Live session started in this chat
+    pub fn for_chat_mut(&mut self, chat_id: ChatId) -> Option<&mut UnsealSession> {
+        self.sessions.values_mut().find(|s| s.chat_id == chat_id && !s.is_expired())
+    }
+
+    /// Live session waiting for a share from this user; the most recently issued link wins
+    /// if the user joined sessions in several chats.
+    pub fn awaiting_share_from(&mut self, user_id: UserId) -> Option<&mut UnsealSession> {
+        self.sessions
+            .values_mut()
+            .filter(|s| !s.is_expired())
+            .filter_map(|s| s.pending_link_issued(user_id).map(|issued| (issued, s)))
+            .max_by_key(|(issued, _)| *issued)
+            .map(|(_, s)| s)
+    }
+
+    /// All live sessions; they share one vault, so progress applies to every one of them
+    pub fn iter_live_mut(&mut self) -> impl Iterator<Item = &mut UnsealSession> {
+        self.sessions.values_mut().filter(|s| !s.is_expired())
+    }
+
+    /// Session ID and key holder of an unused share link
+    pub fn find_share(&self, share_id: &str) -> Option<(String, UserId)> {
+        self.sessions
+            .values()
+            .filter(|s| !s.is_expired())
+            .find_map(|s| {
+                s.holders
+                    .iter()
+                    .find(|h| h.share_id == share_id && h.identity.is_some())
+                    .map(|h| (s.session_id.clone(), h.user_id))
+            })
+    }
+
+    /// Remove all sessions, e.g. because OpenBao has been unsealed
+    pub fn drain(&mut self) -> Vec<UnsealSession> {
+        self.sessions.drain().map(|(_, s)| s).collect()
+    }
+
+    /// Remove the expired sessions (and with them their identities) and return them
+    pub fn take_expired(&mut self) -> Vec<UnsealSession> {
+        let expired: Vec<String> = self.sessions
+            .values()
+            .filter(|s| s.is_expired())
+            .map(|s| s.session_id.clone())
+            .collect();
+        expired.iter().filter_map(|id| self.sessions.remove(id)).collect()
+    }
+}
+
+pub fn create_unseal_menu(session_id: &str) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("🔑 I hold a key share", format!("{}{}", CALLBACK_UNSEAL_JOIN, session_id)),
+    ]])
+}
+
+/// Reply keyboard that opens the unseal page as Telegram Mini App in the key holder's private
+/// chat. Only Mini Apps started from a reply keyboard can send their result back as
+/// `web_app_data`, inline buttons cannot. `None` if the page is not served over HTTPS.
+pub fn create_share_keyboard(web_url: &str, recipient: &str) -> Option<KeyboardMarkup> {
+    // Telegram passes its own parameters in the fragment, so the recipient goes into the query
+    let url = reqwest::Url::parse_with_params(&format!("{}{}", web_url, MINI_APP_PATH), &[("k", recipient)]).ok()?;
+    if url.scheme() != "https" {
+        return None;
+    }
+    let button = KeyboardButton::new("🔑 Enter unseal share").request(ButtonRequest::WebApp(WebAppInfo { url }));
+    Some(KeyboardMarkup::new(vec![vec![button]]).resize_keyboard(true).one_time_keyboard(true))
+}
+
+// Show the current progress in the session chat
+pub async fn update_unseal_status(bot: &Bot, update: StatusUpdate) {
+    let result = bot.edit_message_text(update.chat_id, update.message_id, update.text)
+        .reply_markup(create_unseal_menu(&update.session_id))
+        .await;
+    if let Err(e) = result {
+        warn!("Failed to update unseal status message: {}", e);
+    }
+}
+
+
+// Message to a key holder whose link is used up or over. In a private chat it also removes the
+// Mini App keyboard, so the button of a dead link does not stay on screen.
+async fn send_to_holder(bot: &Bot, chat_id: ChatId, text: impl Into<String>) -> Result<(), RequestError> {
+    let request = bot.send_message(chat_id, text);
+    if chat_id.is_user() {
+        request.reply_markup(ReplyMarkup::kb_remove()).await?;
+    } else {
+        request.await?;
+    }
+    Ok(())
+}
+
+// Decrypt a key holder's share, hand it to OpenBao and report the progress in the session chat.
+// Returns whether the share completed the unseal. The store is only locked to read and update the sessions, never during requests to OpenBao
+// or Telegram, so a slow node does not hold up the sessions of other chats.
+pub async fn submit_share(
+    bot: &Bot,
+    reply_to: ChatId,
+    user_id: UserId,
+    session_id: &str,
+    ciphertext: &[u8],
+    bao_config: &Option<BaoConfig>,
+    sessions: &Mutex<UnsealSessions>
+) -> Result<bool, Box<dyn Error + Send + Sync>> {
+    let Some(bao) = bao_config else {
+        bot.send_message(reply_to, "OpenBao support is not configured.").await?;
+        return Ok(false);
+    };
+    let (session_chat, name, threshold, decrypted) = {
+        let mut sessions = sessions.lock().await;
+        let Some(session) = sessions.get_mut(session_id) else {
+            return Ok(false);
+        };
+        let decrypted = is_age_ciphertext(ciphertext).then(|| session.decrypt_share(user_id, ciphertext));
+        (session.chat_id, session.holder_name(user_id).unwrap_or_default().to_string(), session.threshold, decrypted)
+    };
+
+    let share = match decrypted {
+        Some(Some(Ok(share))) => share,
+        Some(Some(Err(e))) => {
+            warn!("Unseal session {}: decryption failed for user {}: {}", session_id, user_id.0, e);
+            send_to_holder(bot, reply_to, format!("❌ Decryption failed: {}. Press the button in the session chat for a new link.", e)).await?;
+            return Ok(false);
+        },
+        Some(None) => return Ok(false),
+        None => {
+            warn!("Unseal session {}: message from user {} is not an age ciphertext", session_id, user_id.0);
+            bot.send_message(reply_to, "That was not an age ciphertext. Please encrypt your share in the web app and send the encrypted text.").await?;
+            return Ok(false);
+        }
+    };
+    info!("Unseal session {}: share of user {} decrypted", session_id, user_id.0);
+
+    let results = bao.cluster.unseal_all(share.trim()).await;
+    let report = format_unseal_results(&results);
+    let sealed: Vec<&SealStatusInfo> = results.iter().filter_map(|(_, r)| r.as_ref().ok()).filter(|st| st.sealed).collect();
+    let failed = results.iter().filter(|(_, r)| r.is_err()).count();
+
+    let unsealed = !results.is_empty() && sealed.is_empty() && failed == 0;
+    if failed == results.len() {
+        error!("Unseal session {}: unseal failed on every node", session_id);
+        let text = format!("Unseal failed for the share of {}:\n{}", name, report);
+        if reply_to == session_chat {
+            send_to_holder(bot, reply_to, text).await?;
+        } else {
+            bot.send_message(session_chat, text).await?;
+            send_to_holder(bot, reply_to, format!("Unseal failed:\n{}", report)).await?;
+        }
+    } else if unsealed {
+        info!("Unseal session {}: OpenBao unsealed via Telegram", session_id);
+        // The vault is open: every pending session is done
+        let done = sessions.lock().await.drain();
+        let waiting: Vec<UserId> = done.iter().flat_map(|s| s.pending_holders()).collect();
+        for session in done {
+            let text = if session.session_id == session_id {
+                format!("🔓 OpenBao is now unsealed (last share from {}).\n{}", name, report)
+            } else {
+                "🔓 OpenBao has been unsealed in another session.".to_string()
+            };
+            match session.status_message {
+                Some(message_id) => { bot.edit_message_text(session.chat_id, message_id, text).await?; },
+                None => { bot.send_message(session.chat_id, text).await?; },
+            }
+        }
+        if reply_to != session_chat || reply_to.is_user() {
+            send_to_holder(bot, reply_to, "🔓 Your share completed the unseal. OpenBao is now unsealed.").await?;
+        }
+        for user_id in waiting {
+            if let Err(e) = send_to_holder(bot, user_id.into(), "🔓 OpenBao has been unsealed, your unseal link is no longer needed.").await {
+                warn!("Failed to tell user {} that the unseal is done: {}", user_id.0, e);
+            }
+        }
+    } else {
+        // The node that is furthest behind decides how many shares are still needed
+        let progress = sealed.iter().map(|st| st.progress).min().unwrap_or_default();
+        let threshold = sealed.iter().map(|st| st.t).max().unwrap_or(threshold);
+        info!("Unseal session {}: share submitted (progress {}/{}, {} node(s) failed)", session_id, progress, threshold, failed);
+        let updates: Vec<StatusUpdate> = sessions
+            .lock()
+            .await
+            .iter_live_mut()
+            .filter_map(|session| {
+                session.progress = progress;
+                session.threshold = threshold;
+                session.status_update()
+            })
+            .collect();
+        for update in updates {
+            update_unseal_status(bot, update).await;
+        }
+        if failed > 0 {
+            bot.send_message(session_chat, format!("⚠️ The share of {} could not be submitted to every node:\n{}", name, report)).await?;
+        }
+        if reply_to != session_chat {
+            send_to_holder(bot, reply_to, format!("✅ Share submitted:\n{}", report)).await?;
+        } else if reply_to.is_user() {
+            send_to_holder(bot, reply_to, "✅ Share submitted.").await?;
+        }
+    }
+    Ok(unsealed)
+}
+
+/// One line per node with the result of submitting a share
+pub fn format_unseal_results(results: &[(String, Result<SealStatusInfo, BaoError>)]) -> String {
+    results
+        .iter()
+        .map(|(addr, result)| match result {
+            Ok(status) if status.sealed => format!("🔒 {}: {}/{}", addr, status.progress, status.t),
+            Ok(_) => format!("🔓 {}: unsealed", addr),
+            Err(e) => format!("❌ {}: {}", addr, e),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Background sweeper: drops expired sessions from memory and tells their chat and the key
+/// holders still holding an unused link.
+pub fn spawn_sweeper(bot: Bot, sessions: Arc<Mutex<UnsealSessions>>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            let expired = sessions.lock().await.take_expired();
+            for session in expired {
+                info!("Unseal session {} expired (chat {})", session.session_id, session.chat_id.0);
+                notify_expired(&bot, &session).await;
+            }
+        }
+    });
+}
+
+async fn notify_expired(bot: &Bot, session: &UnsealSession) {
+    let text = format!(
+        "⌛ Unseal session expired at {}/{} shares. Call /unseal again for a new session.",
+        session.progress, session.threshold
+    );
+    // Replace the progress message, which also removes the join button; a new message only
+    // if there is none or it cannot be edited any more
+    let edited = match session.status_message {
+        Some(message_id) => match bot.edit_message_text(session.chat_id, message_id, &text).await {
+            Ok(_) => true,
+            Err(e) => {
+                warn!("Failed to update expired unseal status message: {}", e);
+                false
+            }
+        },
+        None => false,
+    };
+    if !edited && let Err(e) = bot.send_message(session.chat_id, &text).await {
+        warn!("Failed to send unseal expiry message to chat {}: {}", session.chat_id.0, e);
+    }
+    // Also in the session chat itself, the progress message cannot remove the Mini App keyboard
+    for user_id in session.pending_holders() {
+        if let Err(e) = send_to_holder(bot, user_id.into(), "⌛ Your unseal link has expired.").await {
+            warn!("Failed to send unseal expiry message to user {}: {}", user_id.0, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn encrypt(recipient: &str, secret: &str) -> String {
+        let recipient: age::x25519::Recipient = recipient.parse().unwrap();
+        let encryptor = age::Encryptor::with_recipients(std::iter::once(&recipient as &dyn age::Recipient)).unwrap();
+        let mut ciphertext = vec![];
+        let armor = age::armor::ArmoredWriter::wrap_output(&mut ciphertext, age::armor::Format::AsciiArmor).unwrap();
+        let mut writer = encryptor.wrap_output(armor).unwrap();
+        writer.write_all(secret.as_bytes()).unwrap();
+        writer.finish().unwrap().finish().unwrap();
+        String::from_utf8(ciphertext).unwrap()
+    }
+
+    #[test]
+    fn each_holder_decrypts_only_with_own_key() {
+        let mut session = UnsealSession::new(ChatId(-1), 0, 3, UNSEAL_SESSION_TIMEOUT);
+        let alice = session.join(UserId(1), "Alice").unwrap();
+        let bob = session.join(UserId(2), "Bob").unwrap();
+        assert_ne!(alice.recipient, bob.recipient);
+
+        // Bob's share encrypted for Alice's link is rejected and Bob needs a new link
+        assert!(session.decrypt_share(UserId(2), encrypt(&alice.recipient, "share-b").as_bytes()).unwrap().is_err());
+        assert!(session.pending_link_issued(UserId(2)).is_none());
+
+        assert_eq!(session.decrypt_share(UserId(1), encrypt(&alice.recipient, "share-a").as_bytes()).unwrap().unwrap(), "share-a");
+        assert!(session.join(UserId(1), "Alice").is_none());
+        assert!(session.decrypt_share(UserId(3), b"x").is_none());
+
+        let bob = session.join(UserId(2), "Bob").unwrap();
+        assert!(session.pending_link_issued(UserId(2)).is_some());
+        assert!(session.decrypt_share(UserId(2), encrypt(&bob.recipient, "share-b").as_bytes()).unwrap().is_ok());
+    }
+
+    #[test]
+    fn sessions_of_different_chats_coexist_and_expire_independently() {
+        let mut store = UnsealSessions::default();
+        let mut group = UnsealSession::new(ChatId(-1), 0, 3, UNSEAL_SESSION_TIMEOUT);
+        group.join(UserId(1), "Alice").unwrap();
+        let group_id = group.session_id.clone();
+        let mut private = UnsealSession::new(ChatId(2), 0, 3, Duration::ZERO);
+        private.join(UserId(1), "Alice").unwrap();
+        let private_id = private.session_id.clone();
+        let link = group.join(UserId(3), "Carol").unwrap();
+        store.insert(group);
+        store.insert(private);
+        assert_eq!(store.find_share(&link.share_id), Some((group_id.clone(), UserId(3))));
+        assert_eq!(store.find_share("unknown"), None);
+
+        // The expired session is invisible to lookups and removed by the sweep
+        assert!(store.get_mut(&private_id).is_none());
+        assert!(store.for_chat_mut(ChatId(2)).is_none());
+        assert_eq!(store.awaiting_share_from(UserId(1)).unwrap().session_id, group_id);
+        let expired = store.take_expired();
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].session_id, private_id);
+        assert_eq!(expired[0].pending_holders().collect::<Vec<_>>(), vec![UserId(1)]);
+
+        assert!(store.for_chat_mut(ChatId(-1)).is_some());
+        assert!(store.take_expired().is_empty());
+    }
+
+    #[test]
+    fn mini_app_button_needs_https() {
+        let keyboard = create_share_keyboard("https://unseal.example.com", "age1abc").unwrap();
+        match &keyboard.keyboard[0][0].request {
+            Some(ButtonRequest::WebApp(info)) => assert_eq!(info.url.as_str(), "https://unseal.example.com/app?k=age1abc"),
+            other => panic!("unexpected button request {:?}", other),
+        }
+        assert!(create_share_keyboard("http://10.0.0.5:8080", "age1abc").is_none());
+    }
+
+    #[test]
+    fn reports_unseal_result_per_node() {
+        let status = |sealed, progress| SealStatusInfo { initialized: true, sealed, progress, t: 3 };
+        let results = vec![
+            ("https://bao1:8200".to_string(), Ok(status(false, 0))),
+            ("https://bao2:8200".to_string(), Ok(status(true, 2))),
+            ("https://bao3:8200".to_string(), Err(BaoError::Api("unseal returned 500".to_string()))),
+        ];
+        assert_eq!(
+            format_unseal_results(&results),
+            "🔓 https://bao1:8200: unsealed\n🔒 https://bao2:8200: 2/3\n❌ https://bao3:8200: OpenBao API error: unseal returned 500"
+        );
+    }
+
+    #[test]
+    fn status_lists_holders_without_shares() {
+        let mut session = UnsealSession::new(ChatId(-1), 1, 3, UNSEAL_SESSION_TIMEOUT);
+        let alice = session.join(UserId(1), "Alice").unwrap();
+        session.join(UserId(2), "Bob").unwrap();
+        session.decrypt_share(UserId(1), encrypt(&alice.recipient, "secret-share").as_bytes()).unwrap().unwrap();
+
+        let text = session.status_text();
+        assert!(text.starts_with("🔐 OpenBao unseal: 1/3 shares"));
+        assert!(text.contains("✅ Alice"));
+        assert!(text.contains("⏳ Bob"));
+        assert!(!text.contains("secret-share"));
+    }
+}
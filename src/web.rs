@@ -0,0 +1,169 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use axum::body::Bytes;
+use axum::extract::{DefaultBodyLimit, Path, State};
+use axum::http::{header, StatusCode};
+use axum::response::{Html, IntoResponse};
+use axum::routing::{get, post};
+use axum::Router;
+use teloxide::prelude::*;
+use tokio::sync::Mutex;
+use log::{error, info, warn};
+
+use crate::bao::is_age_ciphertext;
+use crate::config::Settings;
+use crate::unseal::{self, UnsealSessions};
+use crate::{BaoConfig, NasUnits};
+
+const DEFAULT_AGE_BUNDLE: &str = "web/age.bundle.js";
+const PAGE_TEMPLATE: &str = include_str!("../web/unseal.html");
+const BUNDLE_PLACEHOLDER: &str = "<!-- AGE_BUNDLE -->";
+const WEB_APP_PLACEHOLDER: &str = "<!-- TELEGRAM_WEB_APP -->";
+// Needed by the Mini App to hand the ciphertext back to the bot, never loaded in a normal browser
+const WEB_APP_SCRIPT: &str = r#"<script src="https://telegram.org/js/telegram-web-app.js"></script>"#;
+/// Path of the page variant opened as Telegram Mini App
+pub const MINI_APP_PATH: &str = "/app";
+// An armored share is a few hundred bytes
+const MAX_CIPHERTEXT_BYTES: usize = 16 * 1024;
+
+/// Built-in unseal page: the browser encrypts the share with age and posts the ciphertext back.
+#[derive(Clone)]
+pub struct UnsealWebConfig {
+    listen: SocketAddr,
+    age_bundle: PathBuf,
+}
+
+impl UnsealWebConfig {
+    /// Reads `STB_UNSEAL_WEB_LISTEN` (e.g. `0.0.0.0:8080`) and `STB_UNSEAL_WEB_AGE_BUNDLE`.
+    /// `None` if the listen address is not set, then the page is expected at `STB_UNSEAL_WEB_URL`.
+    pub fn from_settings(settings: &Settings) -> Result<Option<Self>, String> {
+        let Some(listen) = settings.get("STB_UNSEAL_WEB_LISTEN") else {
+            return Ok(None);
+        };
+        let listen = listen.parse()
+            .map_err(|e| format!("STB_UNSEAL_WEB_LISTEN '{}' is not a valid socket address (e.g. 0.0.0.0:8080): {}", listen, e))?;
+        let age_bundle = settings.get("STB_UNSEAL_WEB_AGE_BUNDLE").unwrap_or_else(|| DEFAULT_AGE_BUNDLE.to_string());
+        Ok(Some(UnsealWebConfig { listen, age_bundle: PathBuf::from(age_bundle) }))
+    }
+}
+
+#[derive(Clone)]
+struct WebState {
+    bot: Bot,
+    bao_config: Arc<Option<BaoConfig>>,
+    sessions: Arc<Mutex<UnsealSessions>>,
+    // For reloading the DSM credentials once a share completes the unseal
+    nas_units: Arc<NasUnits>,
+    page: Arc<String>,
+    mini_app_page: Arc<String>,
+}
+
+/// Serve the unseal page next to the dispatcher. Failing to start only disables the page,
+/// ciphertexts can still be sent in Telegram.
+pub async fn spawn(
+    bot: Bot,
+    config: UnsealWebConfig,
+    bao_config: Arc<Option<BaoConfig>>,
+    sessions: Arc<Mutex<UnsealSessions>>,
+    nas_units: Arc<NasUnits>
+) {
+    let bundle = match std::fs::read_to_string(&config.age_bundle) {
+        Ok(bundle) => bundle,
+        Err(e) => {
+            error!("Cannot read age JS bundle {}: {}; unseal page disabled", config.age_bundle.display(), e);
+            return;
+        }
+    };
+    let state = WebState {
+        bot,
+        bao_config,
+        sessions,
+        nas_units,
+        page: Arc::new(render_page(&bundle, false)),
+        mini_app_page: Arc::new(render_page(&bundle, true)),
+    };
+    let app = Router::new()
+        .route("/", get(page_handler))
+        .route(MINI_APP_PATH, get(mini_app_page_handler))
+        .route("/unseal/:share_id", post(share_handler))
+        .layer(DefaultBodyLimit::max(MAX_CIPHERTEXT_BYTES))
+        .with_state(state);
+
+    let listener = match tokio::net::TcpListener::bind(config.listen).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Cannot listen on {}: {}; unseal page disabled", config.listen, e);
+            return;
+        }
+    };
+    info!("Unseal page listening on {}", config.listen);
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app).await {
+            error!("Unseal page server stopped: {}", e);
+        }
+    });
+}
+
+// Inline the age bundle, so the browser page needs no further requests. Only the Mini App
+// variant loads Telegram's WebApp script, the page in a normal browser has no third-party code.
+fn render_page(bundle: &str, mini_app: bool) -> String {
+    // A "</script" inside the bundle would end the inline script early
+    let script = format!("<script>\n{}\n</script>", bundle.replace("</script", "<\\/script"));
+    PAGE_TEMPLATE
+        .replace(BUNDLE_PLACEHOLDER, &script)
+        .replace(WEB_APP_PLACEHOLDER, if mini_app { WEB_APP_SCRIPT } else { "" })
+}
+
+async fn page_handler(State(state): State<WebState>) -> impl IntoResponse {
+    ([(header::CACHE_CONTROL, "no-store")], Html(state.page.to_string()))
+}
+
+async fn mini_app_page_handler(State(state): State<WebState>) -> impl IntoResponse {
+    ([(header::CACHE_CONTROL, "no-store")], Html(state.mini_app_page.to_string()))
+}
+
+// The ciphertext for one key holder's link; the result is reported to the holder in Telegram
+async fn share_handler(
+    State(state): State<WebState>,
+    Path(share_id): Path<String>,
+    body: Bytes
+) -> (StatusCode, &'static str) {
+    let ciphertext = body.trim_ascii();
+    if !is_age_ciphertext(ciphertext) {
+        return (StatusCode::BAD_REQUEST, "That is not an age ciphertext.");
+    }
+
+    let found = state.sessions.lock().await.find_share(&share_id);
+    let Some((session_id, user_id)) = found else {
+        return (StatusCode::NOT_FOUND, "This link has expired or has already been used. Press the button in Telegram for a new link.");
+    };
+    info!("Unseal session {}: share of user {} received via the unseal page", session_id, user_id.0);
+
+    match unseal::submit_share(&state.bot, ChatId::from(user_id), user_id, &session_id, ciphertext, &state.bao_config, &state.sessions).await {
+        Ok(unsealed) => {
+            if unsealed {
+                state.nas_units.reload_credentials().await;
+            }
+            (StatusCode::OK, "Share received. The bot reports the result in Telegram.")
+        },
+        Err(e) => {
+            warn!("Unseal session {}: failed to report the result of a web share: {}", session_id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Share received, but the bot could not report the result in Telegram.")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn page_inlines_age_bundle() {
+        let page = render_page("var age={};/*</script>*/", false);
+        assert!(!page.contains(BUNDLE_PLACEHOLDER));
+        assert!(page.contains("<script>\nvar age={};/*<\\/script>*/\n</script>"));
+        assert!(!page.contains("telegram.org"));
+        assert!(render_page("", true).contains(WEB_APP_SCRIPT));
+    }
+}
@@ -0,0 +1,89 @@
+use std::fmt;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use tokio::net::UdpSocket;
+
+// Port of the magic packet when the broadcast address has none ("discard")
+const DEFAULT_WOL_PORT: u16 = 9;
+
+/// Where to send the Wake-on-LAN packet for one NAS
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WolTarget {
+    pub mac: [u8; 6],
+    pub broadcast: SocketAddr,
+}
+
+impl WolTarget {
+    /// From `<PREFIX>_WOL_MAC` (`00:11:32:aa:bb:cc`, `-` works as well) and the optional
+    /// `<PREFIX>_WOL_BROADCAST` (`192.168.1.255` or `192.168.1.255:7`, default 255.255.255.255:9).
+    pub fn parse(mac: &str, broadcast: Option<&str>) -> Result<Self, String> {
+        let mac = parse_mac(mac).ok_or_else(|| format!("'{}' is not a MAC address (e.g. 00:11:32:aa:bb:cc)", mac))?;
+        let broadcast = match broadcast.map(str::trim) {
+            None => SocketAddrV4::new(Ipv4Addr::BROADCAST, DEFAULT_WOL_PORT).into(),
+            Some(value) => value
+                .parse::<SocketAddr>()
+                .or_else(|_| value.parse::<Ipv4Addr>().map(|ip| SocketAddrV4::new(ip, DEFAULT_WOL_PORT).into()))
+                .map_err(|_| format!("'{}' is not a broadcast address (e.g. 192.168.1.255)", value))?,
+        };
+        Ok(WolTarget { mac, broadcast })
+    }
+
+    /// Send the magic packet once
+    pub async fn wake(&self) -> std::io::Result<()> {
+        let bind: SocketAddr = if self.broadcast.is_ipv4() { "0.0.0.0:0".parse().unwrap() } else { "[::]:0".parse().unwrap() };
+        let socket = UdpSocket::bind(bind).await?;
+        socket.set_broadcast(true)?;
+        socket.send_to(&magic_packet(&self.mac), self.broadcast).await?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for WolTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mac: Vec<String> = self.mac.iter().map(|b| format!("{:02x}", b)).collect();
+        write!(f, "{} via {}", mac.join(":"), self.broadcast)
+    }
+}
+
+fn parse_mac(value: &str) -> Option<[u8; 6]> {
+    let parts: Vec<&str> = value.trim().split([':', '-']).collect();
+    if parts.len() != 6 {
+        return None;
+    }
+    let mut mac = [0u8; 6];
+    for (byte, part) in mac.iter_mut().zip(parts) {
+        if part.len() != 2 {
+            return None;
+        }
+        *byte = u8::from_str_radix(part, 16).ok()?;
+    }
+    Some(mac)
+}
+
+// Six 0xff bytes followed by the MAC address 16 times
+fn magic_packet(mac: &[u8; 6]) -> [u8; 102] {
+    let mut packet = [0xffu8; 102];
+    for chunk in packet[6..].chunks_mut(6) {
+        chunk.copy_from_slice(mac);
+    }
+    packet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_target_and_builds_magic_packet() {
+        let target = WolTarget::parse("00:11:32:AA:bb:cc", None).unwrap();
+        assert_eq!(target.mac, [0x00, 0x11, 0x32, 0xaa, 0xbb, 0xcc]);
+        assert_eq!(target.broadcast, "255.255.255.255:9".parse().unwrap());
+        assert_eq!(WolTarget::parse("00-11-32-aa-bb-cc", Some("192.168.1.255")).unwrap().broadcast, "192.168.1.255:9".parse().unwrap());
+        assert_eq!(WolTarget::parse("00:11:32:aa:bb:cc", Some("10.0.0.255:7")).unwrap().broadcast, "10.0.0.255:7".parse().unwrap());
+        assert!(WolTarget::parse("00:11:32:aa:bb", None).is_err());
+        assert!(WolTarget::parse("00:11:32:aa:bb:cc", Some("nas.local")).is_err());
+
+        let packet = magic_packet(&target.mac);
+        assert_eq!(packet[..6], [0xff; 6]);
+        assert!(packet[6..].chunks(6).all(|chunk| chunk == target.mac));
+    }
+}